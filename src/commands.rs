@@ -0,0 +1,62 @@
+//! Invocations that are not a conversation, like `well graph`.
+//!
+//! A prompt that starts with the name of one goes after `--`, as in `well -- commit what?`.
+use crate::error::Error;
+use crate::{env, io, openai};
+
+mod bisect;
+mod changelog;
+mod commit;
+mod graph;
+mod hotspots;
+mod review;
+
+/// Run the subcommand named by the first argument, if there is one by that name.
+pub async fn run(args: &[String]) -> Option<Result<(), Error>> {
+    let (name, rest) = args.split_first()?;
+    match name.as_str() {
+        "bisect" => Some(bisect::run(rest).await),
+        "changelog" => Some(changelog::run(rest).await),
+        "commit" => Some(commit::run(rest).await),
+        "graph" => Some(graph::run(rest)),
+        "hotspots" => Some(hotspots::run(rest)),
+        "review" => Some(review::run(rest).await),
+        _ => None,
+    }
+}
+
+/// The client and the model name, from the same environment the conversation uses.
+fn connect() -> Result<(openai::Chat, String), Error> {
+    let (api_base, model, secret) = env::vars();
+    if api_base.is_none() && secret.is_none() {
+        return Err("expected env `OPENAI_API_KEY` to be available".into());
+    }
+    let api_base = api_base.as_deref().unwrap_or(env::DEFAULT_API_BASE);
+    let chat = openai::Chat::new(api_base, secret.as_deref())
+        .map_err(|err| err.to_string())?
+        .with_retry_notice(io::set_throbber_status);
+    let model = model.unwrap_or_else(|| env::DEFAULT_MODEL.into());
+    Ok((chat, model))
+}
+
+/// The model may wrap its answer in a fence despite being asked not to.
+fn unfenced(reply: &str) -> &str {
+    let reply = reply.trim();
+    let Some(rest) = reply.strip_prefix("```") else {
+        return reply;
+    };
+    let rest = rest.split_once('\n').map_or("", |(_, rest)| rest);
+    rest.strip_suffix("```").unwrap_or(rest).trim()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fences_are_dropped() {
+        assert_eq!(unfenced("fix: a\n"), "fix: a");
+        assert_eq!(unfenced("```\nfix: a\n\nbody\n```"), "fix: a\n\nbody");
+        assert_eq!(unfenced("```text\nfix: a\n```\n"), "fix: a");
+    }
+}
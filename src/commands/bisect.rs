@@ -0,0 +1,317 @@
+//! `well bisect <good> <bad> [--] <command>...`
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use colored::Colorize;
+use git2::{Oid, Repository, Sort, WorktreeAddOptions, WorktreePruneOptions};
+
+use crate::error::Error;
+use crate::functions::show_commit_with_hash;
+use crate::io;
+use crate::openai::{self, VecOfMessages as _};
+
+/// How much of the failing output to show the model, from the end.
+const MAX_OUTPUT_LINES: usize = 80;
+
+/// The exit code that means the commit cannot be tested, the way `git bisect run` has it.
+const SKIP_EXIT_CODE: i32 = 125;
+
+/// A checkout of its own to test the commits in, so the user's one is not touched.
+/// It goes away, with the branch it needed, when dropped.
+struct Scratch<'repo> {
+    repo: &'repo Repository,
+    name: String,
+    path: PathBuf,
+    checkout: Repository,
+}
+
+impl<'repo> Scratch<'repo> {
+    fn new(repo: &'repo Repository, start: Oid) -> Result<Self, String> {
+        let name = format!("well-bisect-{}", std::process::id());
+        let path = std::env::temp_dir().join(&name);
+        let commit = repo.find_commit(start).map_err(|err| err.to_string())?;
+        let branch = repo
+            .branch(&name, &commit, true)
+            .map_err(|err| err.to_string())?;
+        let checkout = (|| {
+            let mut options = WorktreeAddOptions::new();
+            options.reference(Some(branch.get()));
+            let worktree = repo.worktree(&name, &path, Some(&options))?;
+            Repository::open_from_worktree(&worktree)
+        })();
+        match checkout {
+            Ok(checkout) => Ok(Self {
+                repo,
+                name,
+                path,
+                checkout,
+            }),
+            Err(err) => {
+                remove(repo, &name, &path);
+                Err(err.to_string())
+            }
+        }
+    }
+
+    /// Check out the commit, throwing away whatever the last test left behind.
+    fn check_out(&self, id: Oid) -> Result<(), String> {
+        self.checkout
+            .set_head_detached(id)
+            .map_err(|err| err.to_string())?;
+        self.checkout
+            .checkout_head(Some(
+                git2::build::CheckoutBuilder::new()
+                    .force()
+                    .remove_untracked(true),
+            ))
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Drop for Scratch<'_> {
+    fn drop(&mut self) {
+        remove(self.repo, &self.name, &self.path);
+    }
+}
+
+/// Remove whatever there is of the worktree and its branch, as far as it got to be made.
+fn remove(repo: &Repository, name: &str, path: &Path) {
+    if let Ok(worktree) = repo.find_worktree(name) {
+        let mut options = WorktreePruneOptions::new();
+        options.valid(true).working_tree(true);
+        worktree.prune(Some(&mut options)).ok();
+    }
+    std::fs::remove_dir_all(path).ok();
+    if let Ok(mut branch) = repo.find_branch(name, git2::BranchType::Local) {
+        branch.delete().ok();
+    }
+}
+
+/// How a commit did on the command.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Verdict {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl Verdict {
+    fn shown(self) -> colored::ColoredString {
+        match self {
+            Verdict::Good => "good".green(),
+            Verdict::Bad => "bad".red(),
+            Verdict::Skip => "skip".dimmed(),
+        }
+    }
+}
+
+/// Run the program with its arguments in the directory, and tell how it went, with what it printed.
+/// The arguments are passed as they are, without a shell to split or expand them.
+fn run_command(command: &[&str], directory: &Path) -> Result<(Verdict, String), String> {
+    let (program, arguments) = command.split_first().ok_or("there is no command to run")?;
+    let output = Command::new(program)
+        .args(arguments)
+        .current_dir(directory)
+        .output()
+        .map_err(|err| format!("cannot run `{}`: {err}", command.join(" ")))?;
+    let verdict = match output.status.code() {
+        Some(0) => Verdict::Good,
+        Some(SKIP_EXIT_CODE) => Verdict::Skip,
+        // Killed by a signal counts as failing, like a crash would.
+        _ => Verdict::Bad,
+    };
+    let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+    printed.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((verdict, printed))
+}
+
+/// The commits that may still be the first bad one, the newest first:
+/// reachable from the bad one, but not from any of the good ones.
+fn candidates(repo: &Repository, bad: Oid, good: &[Oid]) -> Result<Vec<Oid>, String> {
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TOPOLOGICAL)
+        .map_err(|err| err.to_string())?;
+    revwalk.push(bad).map_err(|err| err.to_string())?;
+    for &id in good {
+        revwalk.hide(id).map_err(|err| err.to_string())?;
+    }
+    revwalk
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.to_string())
+}
+
+/// The candidate to test next, about halfway through the ones that can be tested.
+fn midpoint(candidates: &[Oid], bad: Oid, skipped: &HashSet<Oid>) -> Option<Oid> {
+    let testable: Vec<Oid> = candidates
+        .iter()
+        .copied()
+        .filter(|id| *id != bad && !skipped.contains(id))
+        .collect();
+    testable.get(testable.len() / 2).copied()
+}
+
+/// The last lines of the text.
+fn tail(text: &str, lines: usize) -> String {
+    let all: Vec<&str> = text.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// Find the first commit for which the command fails, then explain the likely cause.
+pub async fn run(args: &[String]) -> Result<(), Error> {
+    let mut args = args.iter();
+    let (Some(good), Some(bad)) = (args.next(), args.next()) else {
+        return Err("expected a good and a bad revision, then the command to run".into());
+    };
+    let command: Vec<&str> = args
+        .map(String::as_str)
+        .skip_while(|arg| *arg == "--")
+        .collect();
+    if command.is_empty() {
+        return Err("expected a command that fails on the bad revision".into());
+    }
+
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let resolve = |spec: &str| {
+        repo.revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map(|commit| commit.id())
+            .map_err(|err| format!("cannot resolve `{spec}`: {err}"))
+    };
+    let mut good = vec![resolve(good)?];
+    let mut bad = resolve(bad)?;
+    if !repo
+        .graph_descendant_of(bad, good[0])
+        .map_err(|err| err.to_string())?
+    {
+        return Err("the good revision has to be an ancestor of the bad one".into());
+    }
+    // Where the command runs, relative to the root, as the user is in a subdirectory maybe.
+    let root = repo.workdir().ok_or("cannot bisect in a bare repository")?;
+    let subdirectory = std::env::current_dir()
+        .map_err(|err| err.to_string())?
+        .canonicalize()
+        .map_err(|err| err.to_string())?
+        .strip_prefix(root.canonicalize().map_err(|err| err.to_string())?)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let scratch = Scratch::new(&repo, bad)?;
+    let directory = scratch.path.join(&subdirectory);
+    let short = |id: Oid| id.to_string()[..7].to_string();
+    let summary = |id: Oid| {
+        repo.find_commit(id)
+            .map(|commit| commit.summary().unwrap_or_default().to_string())
+            .unwrap_or_default()
+    };
+
+    // Both ends have to do what they are said to, or the search finds nothing that makes sense.
+    let mut bad_output = String::new();
+    for (id, expected) in [(bad, Verdict::Bad), (good[0], Verdict::Good)] {
+        eprint!(
+            "{} {} {} ",
+            "..".bright_blue().dimmed().bold(),
+            short(id).yellow(),
+            summary(id)
+        );
+        scratch.check_out(id)?;
+        let (verdict, output) = run_command(&command, &directory)?;
+        eprintln!("{}", verdict.shown());
+        let problem = match (expected, verdict) {
+            (Verdict::Bad, Verdict::Bad) => {
+                bad_output = output;
+                continue;
+            }
+            (Verdict::Good, Verdict::Good) => continue,
+            (_, Verdict::Skip) => "cannot test",
+            (Verdict::Bad, _) => "passes at the bad revision",
+            _ => "fails at the good revision",
+        };
+        return Err(format!("the command {problem} {}", short(id)).into());
+    }
+
+    let mut skipped = HashSet::new();
+    loop {
+        let remaining = candidates(&repo, bad, &good)?;
+        let Some(next) = midpoint(&remaining, bad, &skipped) else {
+            break;
+        };
+        let steps = (remaining.len() as f64).log2().ceil();
+        eprint!(
+            "{} {} left, about {steps} steps: {} {} ",
+            "..".bright_blue().dimmed().bold(),
+            remaining.len(),
+            short(next).yellow(),
+            summary(next)
+        );
+        scratch.check_out(next)?;
+        // A commit without the directory the command runs in cannot tell either way.
+        let (verdict, output) = if directory.is_dir() {
+            run_command(&command, &directory)?
+        } else {
+            (Verdict::Skip, String::new())
+        };
+        eprintln!("{}", verdict.shown());
+        match verdict {
+            Verdict::Good => good.push(next),
+            Verdict::Bad => {
+                bad = next;
+                bad_output = output;
+            }
+            Verdict::Skip => {
+                skipped.insert(next);
+            }
+        }
+    }
+
+    let remaining = candidates(&repo, bad, &good)?;
+    if remaining.len() > 1 {
+        let mut message =
+            String::from("the commits that could not be tested leave more than one candidate:\n");
+        for id in remaining {
+            message.push_str(&format!("    {} {}\n", short(id), summary(id)));
+        }
+        return Err(message.into());
+    }
+    drop(scratch);
+    eprintln!();
+    println!("first bad commit: {} {}\n", bad, summary(bad));
+
+    let commit = show_commit_with_hash(&bad.to_string(), None, None)?;
+    let (chat, model) = super::connect()?;
+    let mut messages = Vec::<openai::Message>::new_with_context(openai::BISECT_PROMPT);
+    messages.push_user_message(&format!(
+        "Command: `{}`\n\nThe end of its output at the commit:\n{}\n\nThe commit:\n{commit}",
+        command.join(" "),
+        tail(&bad_output, MAX_OUTPUT_LINES)
+    ));
+    let little_snake = io::start_throbber();
+    let reply = chat
+        .complete(&model, &messages, &serde_json::json!([]))
+        .await
+        .map_err(|err| err.to_string())?;
+    little_snake.stop();
+    if let Some(refusal) = reply.message.refusal {
+        return Err(refusal.into());
+    }
+    println!("{}", reply.message.content.unwrap_or_default().trim());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn midpoint_skips_untestable_commits() {
+        let ids: Vec<Oid> = (1..=5u8)
+            .map(|byte| Oid::from_bytes(&[byte; 20]).unwrap())
+            .collect();
+        // The newest first, the bad one is the first and is never picked.
+        assert_eq!(midpoint(&ids, ids[0], &HashSet::new()), Some(ids[3]));
+        let skipped = HashSet::from([ids[3]]);
+        assert_eq!(midpoint(&ids, ids[0], &skipped), Some(ids[2]));
+        assert_eq!(midpoint(&ids[..1], ids[0], &HashSet::new()), None);
+    }
+}
@@ -0,0 +1,378 @@
+//! `well changelog [--group type|module] [--title TITLE] <from>..<to>`
+use std::collections::HashMap;
+
+use git2::{Commit, Repository, Sort};
+
+use crate::error::Error;
+use crate::io;
+use crate::openai::{self, VecOfMessages as _};
+
+/// Releases larger than this are cut to their newest commits.
+const MAX_COMMITS: usize = 500;
+
+/// How much of each message body to pass on besides the summary.
+const MAX_BODY_CHARS: usize = 300;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Grouping {
+    Type,
+    Module,
+}
+
+impl std::str::FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "type" => Ok(Grouping::Type),
+            "module" => Ok(Grouping::Module),
+            _ => Err(format!(
+                "unknown grouping `{s}`, expected one of `type`, `module`"
+            )),
+        }
+    }
+}
+
+/// `type(scope)!: summary`, picked apart.
+struct Conventional<'a> {
+    kind: &'a str,
+    breaking: bool,
+}
+
+fn conventional(summary: &str) -> Option<Conventional<'_>> {
+    let (head, rest) = summary.split_once(':')?;
+    if !rest.starts_with(' ') {
+        return None;
+    }
+    let (head, breaking) = match head.strip_suffix('!') {
+        Some(head) => (head, true),
+        None => (head, false),
+    };
+    let kind = match head.split_once('(') {
+        Some((kind, scope)) => scope.ends_with(')').then_some(kind)?,
+        None => head,
+    };
+    let is_word = !kind.is_empty() && kind.chars().all(|char| char.is_ascii_alphabetic());
+    is_word.then_some(Conventional { kind, breaking })
+}
+
+/// The heading for a conventional commit type.
+fn heading_of_type(kind: &str, breaking: bool) -> &'static str {
+    if breaking {
+        return "Breaking changes";
+    }
+    match kind.to_lowercase().as_str() {
+        "feat" | "feature" => "Features",
+        "fix" | "bugfix" => "Bug fixes",
+        "perf" => "Performance",
+        "docs" | "doc" => "Documentation",
+        "refactor" | "style" => "Refactoring",
+        "test" | "tests" | "build" | "ci" | "chore" => "Maintenance",
+        _ => "Other changes",
+    }
+}
+
+/// The module a path belongs to: its first directory, or the first two under `src` and the like.
+fn module_of_path(path: &std::path::Path) -> String {
+    let directories: Vec<&str> = path
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.iter())
+        .filter_map(|part| part.to_str())
+        .collect();
+    match directories.as_slice() {
+        [] => "Other changes".into(),
+        [root @ ("src" | "lib" | "crates" | "packages" | "apps"), module, ..] => {
+            format!("{root}/{module}")
+        }
+        // `src/io.rs` is the same module as `src/io/`.
+        [root @ ("src" | "lib")] => match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) => format!("{root}/{stem}"),
+            None => root.to_string(),
+        },
+        [first, ..] => first.to_string(),
+    }
+}
+
+/// The module most of the commit's changes are in.
+fn module_of_commit(repo: &Repository, commit: &Commit) -> String {
+    let tree = commit.tree().ok();
+    let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+    let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), tree.as_ref(), None) else {
+        return "Other changes".into();
+    };
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for delta in diff.deltas() {
+        if let Some(path) = delta.new_file().path().or(delta.old_file().path()) {
+            *counts.entry(module_of_path(path)).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by(|one, another| one.1.cmp(&another.1).then_with(|| another.0.cmp(&one.0)))
+        .map(|(module, _)| module)
+        .unwrap_or_else(|| "Other changes".into())
+}
+
+/// Where to see a commit in the browser, from the `origin` remote, if it is a known kind of host.
+fn web_url(remote: &str) -> Option<String> {
+    let remote = remote.trim().trim_end_matches('/').trim_end_matches(".git");
+    let (host, path) = if let Some(rest) = remote.strip_prefix("git@") {
+        rest.split_once(':')?
+    } else {
+        let rest = remote
+            .strip_prefix("https://")
+            .or_else(|| remote.strip_prefix("http://"))
+            .or_else(|| remote.strip_prefix("ssh://"))?;
+        let rest = rest.rsplit_once('@').map_or(rest, |(_, rest)| rest);
+        rest.split_once('/')?
+    };
+    // A port only matters for ssh, the web is on the default one.
+    let host = host.split(':').next()?;
+    Some(format!("https://{host}/{path}/commit"))
+}
+
+/// Turn the short hashes the model mentions into links to the commits.
+fn link_hashes(notes: &str, hashes: &[String], base: Option<&str>) -> String {
+    let pattern = regex::Regex::new(r"\b[0-9a-f]{7,40}\b").unwrap();
+    pattern
+        .replace_all(notes, |captures: &regex::Captures| {
+            let short = &captures[0];
+            match (hashes.iter().find(|hash| hash.starts_with(short)), base) {
+                (Some(hash), Some(base)) => format!("[{short}]({base}/{hash})"),
+                (Some(_), None) => format!("`{short}`"),
+                (None, _) => short.to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// The ends of `<from>..<to>`, with `HEAD` for a missing end,
+/// and whether it was `<from>...<to>`, which starts from where the two forked.
+fn parse_range(range: &str) -> Result<(&str, &str, bool), String> {
+    let (from, to, merge_base) = match range.split_once("...") {
+        Some((from, to)) => (from, to, true),
+        None => match range.split_once("..") {
+            Some((from, to)) => (from, to, false),
+            None => (range, "", false),
+        },
+    };
+    if from.is_empty() || to.starts_with('.') {
+        return Err(format!("expected `<from>..<to>`, not `{range}`"));
+    }
+    let to = if to.is_empty() { "HEAD" } else { to };
+    Ok((from, to, merge_base))
+}
+
+/// Write the release notes for the commits in the range, as a section of `CHANGELOG.md`.
+pub async fn run(args: &[String]) -> Result<(), Error> {
+    let mut grouping = None;
+    let mut title = None;
+    let mut range = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        match option {
+            "--group" | "--title" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .ok_or(format!("expected a value after `{option}`"))?,
+                };
+                if option == "--group" {
+                    grouping = Some(value.parse()?);
+                } else {
+                    title = Some(value.to_string());
+                }
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`").into()),
+            _ => range = Some(arg.as_str()),
+        }
+    }
+    let range = range.ok_or("expected a range of commits, like `v1.0.0..HEAD`")?;
+    let (from, to, merge_base) = parse_range(range)?;
+
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let resolve = |spec: &str| {
+        repo.revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|err| format!("cannot resolve `{spec}`: {err}"))
+    };
+    let (mut from_commit, to_commit) = (resolve(from)?, resolve(to)?);
+    if merge_base {
+        from_commit = repo
+            .merge_base(from_commit.id(), to_commit.id())
+            .and_then(|base| repo.find_commit(base))
+            .map_err(|err| format!("`{from}` and `{to}` have no merge base: {err}"))?;
+    }
+
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)
+        .map_err(|err| err.to_string())?;
+    revwalk
+        .push(to_commit.id())
+        .map_err(|err| err.to_string())?;
+    revwalk
+        .hide(from_commit.id())
+        .map_err(|err| err.to_string())?;
+    let mut commits = Vec::new();
+    for id in revwalk {
+        let commit = repo
+            .find_commit(id.map_err(|err| err.to_string())?)
+            .map_err(|err| err.to_string())?;
+        // Merges repeat what their branches did.
+        if commit.parent_count() <= 1 {
+            commits.push(commit);
+        }
+    }
+    if commits.is_empty() {
+        return Err(format!("no commits in `{from}..{to}`").into());
+    }
+    // The newest commits are the ones the notes are most likely written for.
+    if commits.len() > MAX_COMMITS {
+        let dropped = commits.len() - MAX_COMMITS;
+        commits.drain(..dropped);
+        eprintln!("well: left out the {dropped} oldest commits, only the newest {MAX_COMMITS} fit");
+    }
+
+    let grouping = grouping.unwrap_or_else(|| {
+        let typed = commits
+            .iter()
+            .filter(|commit| conventional(commit.summary().unwrap_or_default()).is_some())
+            .count();
+        if typed * 2 >= commits.len() {
+            Grouping::Type
+        } else {
+            Grouping::Module
+        }
+    });
+
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    let mut hashes = Vec::new();
+    for commit in &commits {
+        let summary = commit.summary().unwrap_or_default();
+        let body = commit.body().unwrap_or_default();
+        let group = match grouping {
+            Grouping::Type => match conventional(summary) {
+                Some(parsed) => {
+                    let breaking = parsed.breaking || body.contains("BREAKING CHANGE");
+                    heading_of_type(parsed.kind, breaking).to_string()
+                }
+                None => "Other changes".into(),
+            },
+            Grouping::Module => module_of_commit(&repo, commit),
+        };
+        let hash = commit.id().to_string();
+        let mut entry = format!("{} {summary}", &hash[..7]);
+        let body: String = body.chars().take(MAX_BODY_CHARS).collect();
+        if !body.trim().is_empty() {
+            entry.push_str(&format!("\n    {}", body.trim().replace('\n', "\n    ")));
+        }
+        hashes.push(hash);
+        match groups.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, entries)) => entries.push(entry),
+            None => groups.push((group, vec![entry])),
+        }
+    }
+    let mut request = String::new();
+    for (group, entries) in &groups {
+        request.push_str(&format!("{group}:\n"));
+        for entry in entries {
+            request.push_str(&format!("  {entry}\n"));
+        }
+        request.push('\n');
+    }
+
+    let (chat, model) = super::connect()?;
+    let mut messages = Vec::<openai::Message>::new_with_context(openai::CHANGELOG_PROMPT);
+    messages.push_user_message(&request);
+    let little_snake = io::start_throbber();
+    let reply = chat
+        .complete(&model, &messages, &serde_json::json!([]))
+        .await
+        .map_err(|err| err.to_string())?;
+    little_snake.stop();
+    if let Some(refusal) = reply.message.refusal {
+        return Err(refusal.into());
+    }
+    let content = reply.message.content.unwrap_or_default();
+
+    let base = repo
+        .find_remote("origin")
+        .ok()
+        .and_then(|remote| remote.url().and_then(web_url));
+    let notes = link_hashes(super::unfenced(&content), &hashes, base.as_deref());
+    let title = title.unwrap_or_else(|| match to {
+        "HEAD" => "Unreleased".into(),
+        to => to.to_string(),
+    });
+    let date = chrono::DateTime::from_timestamp(to_commit.time().seconds(), 0)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    println!("## {title} ({date})\n\n{notes}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conventional_summaries() {
+        let parsed = conventional("feat(io)!: stream replies").unwrap();
+        assert_eq!((parsed.kind, parsed.breaking), ("feat", true));
+        assert_eq!(conventional("fix: off by one").unwrap().kind, "fix");
+        assert!(conventional("Merge branch 'main'").is_none());
+        assert!(conventional("[user-1] add a thing").is_none());
+        assert!(conventional("fix:no space").is_none());
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("v1.0..v2.0"), Ok(("v1.0", "v2.0", false)));
+        assert_eq!(parse_range("v1.0.."), Ok(("v1.0", "HEAD", false)));
+        assert_eq!(parse_range("v1.0"), Ok(("v1.0", "HEAD", false)));
+        assert_eq!(parse_range("v1.0...v2.0"), Ok(("v1.0", "v2.0", true)));
+        assert!(parse_range("..HEAD").is_err());
+        assert!(parse_range("...HEAD").is_err());
+        assert!(parse_range("v1.0....v2.0").is_err());
+    }
+
+    #[test]
+    fn modules_of_paths() {
+        let module = |path: &str| module_of_path(std::path::Path::new(path));
+        assert_eq!(module("src/functions/blame.rs"), "src/functions");
+        assert_eq!(module("src/functions.rs"), "src/functions");
+        assert_eq!(module("docs/guide/intro.md"), "docs");
+        assert_eq!(module("README.md"), "Other changes");
+    }
+
+    #[test]
+    fn links_to_commits() {
+        assert_eq!(
+            web_url("git@github.com:owner/repo.git").as_deref(),
+            Some("https://github.com/owner/repo/commit")
+        );
+        assert_eq!(
+            web_url("https://user@gitlab.com/group/sub/repo").as_deref(),
+            Some("https://gitlab.com/group/sub/repo/commit")
+        );
+        assert_eq!(web_url("/srv/git/repo.git"), None);
+
+        let hashes = vec!["1a2b3c4d5e6f".to_string()];
+        assert_eq!(
+            link_hashes(
+                "- Fixed it (1a2b3c4, 7777777)",
+                &hashes,
+                Some("https://h/r/commit")
+            ),
+            "- Fixed it ([1a2b3c4](https://h/r/commit/1a2b3c4d5e6f), 7777777)"
+        );
+        assert_eq!(link_hashes("(1a2b3c4)", &hashes, None), "(`1a2b3c4`)");
+    }
+}
@@ -0,0 +1,102 @@
+//! `well commit [--write] [file [source [commit]]]`
+use crate::error::Error;
+use crate::functions::{list_commits_in_current_repo, staged_changes, LogFilter};
+use crate::io;
+use crate::openai::{self, VecOfMessages as _};
+
+/// How many lines of the staged patch to send, all the files together.
+const PATCH_BUDGET: usize = 1500;
+
+/// How many recent commits to show the model as a reference for the style.
+const STYLE_REFERENCE_COMMITS: usize = 15;
+
+/// Draft a commit message for what is staged.
+///
+/// Prints it, or with `--write` puts it into `.git/COMMIT_EDITMSG`.
+/// Given a file, the way git calls a `prepare-commit-msg` hook, puts it on top of that file,
+/// unless git says the message already comes from somewhere else.
+pub async fn run(args: &[String]) -> Result<(), Error> {
+    let mut write = false;
+    let mut positional = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--write" => write = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`").into()),
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let (file, source) = (positional.first(), positional.get(1));
+    // `-m`, `-F`, merges, squashes and amends bring their own message.
+    if source.is_some_and(|source| *source != "template") {
+        return Ok(());
+    }
+
+    let message = match draft().await {
+        Ok(message) => message,
+        // A failing hook would stop the commit, so the user gets to write the message instead.
+        Err(Error(err)) if file.is_some() => {
+            eprintln!("well: no message drafted: {err}");
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+
+    let location = match (file, write) {
+        (Some(file), _) => Some(std::path::PathBuf::from(file)),
+        (None, true) => {
+            let repo = git2::Repository::discover(".").map_err(|err| err.to_string())?;
+            Some(repo.path().join("COMMIT_EDITMSG"))
+        }
+        (None, false) => None,
+    };
+    let Some(location) = location else {
+        println!("{message}");
+        return Ok(());
+    };
+    // Keep what git put there, like the commented-out status, below the message.
+    let existing = match file {
+        Some(_) => std::fs::read_to_string(&location).unwrap_or_default(),
+        None => String::new(),
+    };
+    if let Err(err) = std::fs::write(&location, format!("{message}\n{existing}")) {
+        if file.is_none() {
+            return Err(err.to_string().into());
+        }
+        eprintln!("well: could not write the message: {err}");
+    }
+    Ok(())
+}
+
+/// Ask the model for a message that fits the staged changes and the style of the history.
+async fn draft() -> Result<String, Error> {
+    let changes = staged_changes(PATCH_BUDGET)?.ok_or("nothing is staged to commit")?;
+    // An unborn branch has no history to learn the style from.
+    let history = list_commits_in_current_repo(&LogFilter {
+        max_count: Some(STYLE_REFERENCE_COMMITS),
+        first_parent: true,
+        ..LogFilter::default()
+    })
+    .unwrap_or_default();
+
+    let mut messages = Vec::<openai::Message>::new_with_context(openai::COMMIT_MESSAGE_PROMPT);
+    messages.push_user_message(&format!(
+        "Recent commits:\n{history}\nStaged changes:\n{changes}"
+    ));
+
+    let (chat, model) = super::connect()?;
+    let little_snake = io::start_throbber();
+    let reply = chat
+        .complete(&model, &messages, &serde_json::json!([]))
+        .await;
+    little_snake.stop();
+    let reply = reply.map_err(|err| err.to_string())?;
+    if let Some(refusal) = reply.message.refusal {
+        return Err(refusal.into());
+    }
+    let content = reply.message.content.unwrap_or_default();
+    let message = super::unfenced(&content);
+    if message.is_empty() {
+        return Err("the model did not suggest a message".into());
+    }
+    Ok(message.to_string())
+}
@@ -0,0 +1,29 @@
+//! `well graph [--format dot|json|mermaid] [path]`
+use std::path::Path;
+
+use crate::error::Error;
+use crate::functions::{GraphFormat, ModuleGraph};
+
+/// Print the module dependency graph of the given directory.
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut format = GraphFormat::Dot;
+    let mut path = ".";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--format=") {
+            format = value.parse()?;
+        } else if arg == "--format" {
+            let value = args.next().ok_or("expected a format after `--format`")?;
+            format = value.parse()?;
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option `{arg}`").into());
+        } else {
+            path = arg;
+        }
+    }
+
+    let graph = ModuleGraph::of_directory(Path::new(path)).map_err(|err| err.to_string())?;
+    let edges = graph.filtered(None, None);
+    print!("{}", graph.render(&edges, format));
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! `well hotspots [--days N] [--limit N] [path]`
+use crate::error::Error;
+use crate::functions::hotspots_report;
+
+/// Print the files and functions that change often and are complex, riskiest first.
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut days = 365;
+    let mut limit = 20;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) => (option, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        match option {
+            "--days" | "--limit" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .ok_or(format!("expected a number after `{option}`"))?,
+                };
+                let value: usize = value
+                    .parse()
+                    .map_err(|_| format!("expected a number after `{option}`, got `{value}`"))?;
+                if option == "--days" {
+                    days = u32::try_from(value).map_err(|err| err.to_string())?;
+                } else {
+                    limit = value.max(1);
+                }
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`").into()),
+            _ => path = Some(arg.as_str()),
+        }
+    }
+
+    print!("{}", hotspots_report(path, days, limit)?);
+    Ok(())
+}
@@ -0,0 +1,294 @@
+//! `well review [--format text|json|quickfix] [--fail-on info|warning|error] [range]`
+use colored::Colorize;
+
+use crate::error::Error;
+use crate::functions::{apply, diff_between_revisions, diff_of_working_tree};
+use crate::io;
+use crate::openai::{self, VecOfMessages as _};
+
+/// How many rounds of reading the model gets before it has to answer.
+const MAX_STEPS: usize = 40;
+
+#[derive(
+    serde::Deserialize, serde::Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug,
+)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::str::FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "unknown severity `{s}`, expected one of `info`, `warning`, `error`"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+struct Finding {
+    file: String,
+    #[serde(default)]
+    line: usize,
+    severity: Severity,
+    message: String,
+}
+
+enum Format {
+    Text,
+    Json,
+    Quickfix,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "quickfix" => Ok(Format::Quickfix),
+            _ => Err(format!(
+                "unknown review format `{s}`, expected one of `text`, `json`, `quickfix`"
+            )),
+        }
+    }
+}
+
+/// The findings in the final reply, which may have some prose around the array.
+fn parse_findings(reply: &str) -> Result<Vec<Finding>, String> {
+    let reply = super::unfenced(reply);
+    let array = match (reply.find('['), reply.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => reply,
+    };
+    serde_json::from_str(array).map_err(|err| format!("cannot read the findings: {err}\n{reply}"))
+}
+
+/// Findings as lines an editor can jump through, like `vim -q` or `M-x compile` do.
+fn quickfix(findings: &[Finding]) -> String {
+    let mut result = String::new();
+    for finding in findings {
+        let message = finding.message.replace('\n', " ");
+        result.push_str(&format!(
+            "{}:{}: {}: {message}\n",
+            finding.file,
+            finding.line.max(1),
+            finding.severity
+        ));
+    }
+    result
+}
+
+/// Findings for a person to read, grouped by file.
+fn text(findings: &[Finding]) -> String {
+    let mut result = String::new();
+    let mut last_file = None;
+    for finding in findings {
+        if last_file != Some(&finding.file) {
+            if last_file.is_some() {
+                result.push('\n');
+            }
+            result.push_str(&format!("{}\n", finding.file.bold()));
+            last_file = Some(&finding.file);
+        }
+        let severity = match finding.severity {
+            Severity::Info => finding.severity.to_string().dimmed(),
+            Severity::Warning => finding.severity.to_string().yellow(),
+            Severity::Error => finding.severity.to_string().red(),
+        };
+        result.push_str(&format!(
+            "  {:>5} {severity}: {}\n",
+            finding.line, finding.message
+        ));
+    }
+    if findings.is_empty() {
+        result.push_str("nothing to report\n");
+    }
+    result
+}
+
+/// What to review when the user does not say: the uncommitted changes if there are any,
+/// or else what the branch adds to `main`.
+fn default_subject() -> Result<Option<String>, Error> {
+    let repo = git2::Repository::discover(".").map_err(|err| err.to_string())?;
+    let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let dirty = repo
+        .diff_tree_to_workdir_with_index(head.as_ref(), None)
+        .map_err(|err| err.to_string())?;
+    if dirty.deltas().len() > 0 {
+        return Ok(None);
+    }
+    let main = ["main", "master"]
+        .into_iter()
+        .find(|name| repo.revparse_single(name).is_ok())
+        .ok_or("nothing to review: the tree is clean, and there is no `main` to compare with")?;
+    Ok(Some(format!("{main}...HEAD")))
+}
+
+/// Let the model read its way through the change, and collect what it finds.
+async fn review(range: Option<&str>) -> Result<Vec<Finding>, Error> {
+    let range = match range {
+        Some(range) => Some(range.to_string()),
+        None => default_subject()?,
+    };
+    let request = match &range {
+        Some(range) => format!(
+            "Review the change `{range}`. Pass it to `D` as `from`.\n\n{}",
+            diff_between_revisions(range, None, false, None, true)?
+        ),
+        None => format!(
+            "Review the uncommitted changes in the working tree.\n\n{}",
+            diff_of_working_tree(false, None, true)?
+        ),
+    };
+
+    let (chat, model) = super::connect()?;
+    let tools = openai::read_only_functions();
+    let allowed: Vec<&str> = tools
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|function| function["function"]["name"].as_str())
+        .collect();
+    let mut messages = Vec::<openai::Message>::new_with_context(openai::REVIEW_PROMPT);
+    messages.push_user_message(&request);
+    let no_tools = serde_json::json!([]);
+
+    let mut step = 0;
+    loop {
+        step += 1;
+        // Past the limit, the model has to answer with what it has read so far.
+        let out_of_steps = step > MAX_STEPS;
+        if step == MAX_STEPS + 1 {
+            messages.push_user_message("Stop reading, and reply with the findings now.");
+        }
+        let little_snake = io::start_throbber();
+        let reply = chat
+            .complete(
+                &model,
+                &messages,
+                if out_of_steps { &no_tools } else { &tools },
+            )
+            .await
+            .map_err(|err| err.to_string())?;
+        little_snake.stop();
+        if let Some(refusal) = reply.message.refusal {
+            return Err(refusal.into());
+        }
+        if reply.finish_reason == openai::FinishReason::UsageExceeded {
+            return Err(
+                "the change is too large to review in one go, pass a narrower range".into(),
+            );
+        }
+        let calls = reply.message.tool_calls.unwrap_or_default();
+        let content = reply.message.content.unwrap_or_default();
+        messages.push_assistant_message(&content, &calls);
+        if calls.is_empty() {
+            return Ok(parse_findings(&content)?);
+        }
+
+        io::show_reply("", &calls);
+        for call in &calls {
+            let name = call.function.name.as_str();
+            let result = if allowed.contains(&name) {
+                apply(name, &call.function.arguments)
+            } else {
+                serde_json::json!({ "error": format!("`{name}` is not available in a review") })
+                    .to_string()
+            };
+            messages.push_function_call_result(&call.id, &result);
+        }
+    }
+}
+
+/// Review a change, print the findings, and fail if any of them is severe enough.
+pub async fn run(args: &[String]) -> Result<(), Error> {
+    let mut format = Format::Text;
+    let mut fail_on = Severity::Error;
+    let mut range = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) => (option, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        match option {
+            "--format" | "--fail-on" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .ok_or(format!("expected a value after `{option}`"))?,
+                };
+                if option == "--format" {
+                    format = value.parse()?;
+                } else {
+                    fail_on = value.parse()?;
+                }
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`").into()),
+            _ => range = Some(arg.as_str()),
+        }
+    }
+
+    let mut findings = review(range).await?;
+    findings.sort_by(|one, another| (&one.file, one.line).cmp(&(&another.file, another.line)));
+    match format {
+        Format::Text => print!("{}", text(&findings)),
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&findings).map_err(|err| err.to_string())?
+        ),
+        Format::Quickfix => print!("{}", quickfix(&findings)),
+    }
+
+    let failing = findings
+        .iter()
+        .filter(|finding| finding.severity >= fail_on)
+        .count();
+    if failing > 0 {
+        return Err(format!("{failing} findings at `{fail_on}` or above").into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn findings_are_read_from_the_reply() {
+        let reply = "Here is what I found:\n```json\n[{\"file\": \"src/a.rs\", \"line\": 3, \"severity\": \"warning\", \"message\": \"unwrap\\non input\"}]\n```";
+        let findings = parse_findings(reply).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(
+            quickfix(&findings),
+            "src/a.rs:3: warning: unwrap on input\n"
+        );
+
+        assert!(parse_findings("[]").unwrap().is_empty());
+        assert!(parse_findings("looks good").is_err());
+        assert!(Severity::Error > Severity::Warning);
+    }
+}
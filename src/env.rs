@@ -1,5 +1,11 @@
 use std::env;
 
+/// Where the API is, unless told otherwise.
+pub const DEFAULT_API_BASE: &str = "https://api.openai.com/v1";
+
+/// Which model to talk to, unless told otherwise.
+pub const DEFAULT_MODEL: &str = "gpt-4o";
+
 /// API base from `OPENAI_API_BASE`, if set.
 pub fn api_base_from_env() -> Option<String> {
     None.or_else(|| env::var("WELL_OPENAI_API_BASE").ok())
@@ -12,6 +18,12 @@ pub fn model_name_from_env() -> Option<String> {
         .or_else(|| env::var("OPENAI_MODEL").ok())
 }
 
+/// Embedding model name from `OPENAI_EMBEDDING_MODEL`, if set.
+pub fn embedding_model_name_from_env() -> Option<String> {
+    None.or_else(|| env::var("WELL_OPENAI_EMBEDDING_MODEL").ok())
+        .or_else(|| env::var("OPENAI_EMBEDDING_MODEL").ok())
+}
+
 /// Secret key from `OPENAI_API_KEY`, if set.
 pub fn secret_key_from_env() -> Option<String> {
     None.or_else(|| env::var("WELL_OPENAI_SECRET").ok())
@@ -44,10 +56,19 @@ pub fn vars() -> (Option<String>, Option<String>, Option<String>) {
     }
 }
 
-/// Build a prompt from `argv`
+/// `argv` without the program name.
+pub fn args() -> Vec<String> {
+    std::env::args().skip(1).collect()
+}
+
+/// Build a prompt from `argv`, past a leading `--` that keeps it from naming a subcommand
 pub fn prompt_from_args() -> String {
+    let mut args = args();
+    if args.first().is_some_and(|arg| arg == "--") {
+        args.remove(0);
+    }
     let mut prompt = String::new();
-    for arg in std::env::args().skip(1) {
+    for arg in args {
         prompt.push_str(&arg);
         prompt.push(' ');
     }
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 
 use serde_json::json;
 
+mod at_revision;
 mod common;
 
 mod query_ast;
@@ -20,6 +21,60 @@ use list_commits::rpc::list_commits;
 mod show_commit;
 use show_commit::rpc::show_commit;
 
+mod call_graph;
+use call_graph::rpc::call_graph;
+
+mod module_graph;
+use module_graph::rpc::module_graph;
+
+mod annotations;
+use annotations::rpc::list_annotations;
+
+mod chunks;
+
+mod lexical_search;
+use lexical_search::rpc::lexical_search;
+
+mod semantic_search;
+use semantic_search::rpc::semantic_search;
+
+mod blame;
+use blame::rpc::blame;
+
+mod working_tree;
+use working_tree::rpc::{show_status, show_working_diff};
+
+mod revision_diff;
+use revision_diff::rpc::diff_revisions;
+
+mod pickaxe;
+use pickaxe::rpc::pickaxe;
+
+mod refs;
+use refs::rpc::list_refs;
+
+mod function_history;
+use function_history::rpc::function_history;
+
+mod hotspots;
+use hotspots::rpc::hotspots;
+
+mod ownership;
+use ownership::rpc::ownership;
+
+mod commit;
+use commit::rpc::commit;
+
+mod conflicts;
+use conflicts::rpc::{list_conflicts, resolve_conflicts};
+
+pub use hotspots::hotspots_report;
+pub use list_commits::{list_commits_in_current_repo, LogFilter};
+pub use module_graph::{GraphFormat, ModuleGraph};
+pub use revision_diff::diff_between_revisions;
+pub use show_commit::show_commit_with_hash;
+pub use working_tree::{diff_of_working_tree, staged_changes};
+
 use crate::openai::ToolCallRequest;
 
 fn to_json<T, E>(result: Result<T, E>) -> serde_json::value::Value
@@ -41,6 +96,23 @@ pub fn apply(name: &str, arguments: &str) -> String {
         "F" => read_file(arguments),
         "g" => list_commits(arguments),
         "G" => show_commit(arguments),
+        "c" => call_graph(arguments),
+        "m" => module_graph(arguments),
+        "t" => list_annotations(arguments),
+        "s" => lexical_search(arguments),
+        "S" => semantic_search(arguments),
+        "b" => blame(arguments),
+        "w" => show_status(arguments),
+        "W" => show_working_diff(arguments),
+        "D" => diff_revisions(arguments),
+        "k" => pickaxe(arguments),
+        "R" => list_refs(arguments),
+        "h" => function_history(arguments),
+        "H" => hotspots(arguments),
+        "o" => ownership(arguments),
+        "C" => commit(arguments),
+        "x" => list_conflicts(arguments),
+        "X" => resolve_conflicts(arguments),
         _ => Err(format!("no such function: `{name}`")),
     };
 
@@ -0,0 +1,221 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use git2::{Blame, Repository};
+use tree_sitter::Node;
+
+use super::common::{path_in_repo, path_spills_up, walk_files};
+use super::query_ast::parse_source;
+
+pub const DEFAULT_TAGS: &[&str] = &["TODO", "FIXME", "HACK", "XXX", "SAFETY"];
+
+/// A tagged remark left in a comment.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub file: PathBuf,
+    /// One-based.
+    pub line: usize,
+    pub tag: String,
+    pub text: String,
+}
+
+/// Find the tags in a single line of a comment, as whole words only.
+/// Returns the tag and whatever follows it.
+fn find_tag<'line>(line: &'line str, tags: &[String]) -> Option<(String, &'line str)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    for tag in tags {
+        for (position, _) in line.match_indices(tag.as_str()) {
+            let before = line[..position].chars().next_back();
+            let after = line[position + tag.len()..].chars().next();
+            if before.is_some_and(is_word) || after.is_some_and(is_word) {
+                continue;
+            }
+            let text = line[position + tag.len()..]
+                .trim_start_matches(|c: char| c == ':' || c == '-' || c.is_whitespace())
+                .trim_end_matches("*/")
+                .trim();
+            return Some((tag.clone(), text));
+        }
+    }
+    None
+}
+
+/// Call the visitor for every comment in the tree.
+fn visit_comments<'tree>(node: Node<'tree>, visitor: &mut impl FnMut(Node<'tree>)) {
+    if node.kind().contains("comment") {
+        visitor(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        visit_comments(child, visitor);
+    }
+}
+
+/// All the tagged comments in the file, if the file is in a language we can parse.
+fn annotations_of_file(path: &Path, tags: &[String]) -> io::Result<Vec<Annotation>> {
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    let source_code = std::fs::read_to_string(path)?;
+    let Some(tree) = parse_source(ext, &source_code) else {
+        return Ok(Vec::new());
+    };
+
+    let mut result = Vec::new();
+    visit_comments(tree.root_node(), &mut |node| {
+        let text = node.utf8_text(source_code.as_bytes()).unwrap_or_default();
+        for (offset, line) in text.lines().enumerate() {
+            if let Some((tag, text)) = find_tag(line, tags) {
+                result.push(Annotation {
+                    file: path.to_path_buf(),
+                    line: node.start_position().row + offset + 1,
+                    tag,
+                    text: text.to_string(),
+                });
+            }
+        }
+    });
+    Ok(result)
+}
+
+/// `{author, date, hash}` of whoever last touched the line.
+fn attribute(repo: &Repository, blame: &Blame, line: usize) -> String {
+    let Some(hunk) = blame.get_line(line) else {
+        return String::new();
+    };
+    if hunk.final_commit_id().is_zero() {
+        return " {not committed yet}".into();
+    }
+    let hash = repo
+        .find_commit(hunk.final_commit_id())
+        .ok()
+        .and_then(|commit| commit.as_object().short_id().ok())
+        .and_then(|id| id.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let signature = hunk.final_signature();
+    let author = signature.name().unwrap_or_default();
+    let date = chrono::DateTime::from_timestamp(signature.when().seconds(), 0)
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    format!(" {{{author}, {date}, {hash}}}")
+}
+
+/// List the tagged comments under the path, with a tally by tag up front.
+fn list_annotations_with_path(path: &Path, tags: &[String], blame: bool) -> io::Result<String> {
+    let files = if path.is_dir() {
+        walk_files(path)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut annotations = Vec::new();
+    for file in files {
+        // Binary and otherwise unreadable files have nothing to say.
+        if let Ok(found) = annotations_of_file(&file, tags) {
+            annotations.extend(found);
+        }
+    }
+
+    let mut result = String::new();
+    let mut tally = tags.iter().map(|tag| (tag.as_str(), 0)).collect::<Vec<_>>();
+    for annotation in &annotations {
+        if let Some((_, count)) = tally.iter_mut().find(|(tag, _)| *tag == annotation.tag) {
+            *count += 1;
+        }
+    }
+    let tally: Vec<String> = tally
+        .iter()
+        .map(|(tag, count)| format!("{tag}: {count}"))
+        .collect();
+    result.push_str(&tally.join(", "));
+    result.push_str("\n\n");
+
+    let repo = if blame {
+        Repository::discover(".").ok()
+    } else {
+        None
+    };
+    for in_file in annotations.chunk_by(|one, another| one.file == another.file) {
+        let file = &in_file[0].file;
+        // Blame the file as it is on disk, so that the lines being edited
+        // show up as such instead of shifting the attribution around.
+        let committed = repo.as_ref().and_then(|repo| {
+            let path = path_in_repo(repo, file)?;
+            repo.blame_file(&path, None).ok()
+        });
+        let contents = std::fs::read(file).unwrap_or_default();
+        let blame = committed
+            .as_ref()
+            .and_then(|committed| committed.blame_buffer(&contents).ok());
+
+        for Annotation {
+            line, tag, text, ..
+        } in in_file
+        {
+            let attribution = match (repo.as_ref(), blame.as_ref()) {
+                (Some(repo), Some(blame)) => attribute(repo, blame, *line),
+                _ => String::new(),
+            };
+            result.push_str(&format!(
+                "{}:{line} {tag} {text}{attribution}\n",
+                file.display()
+            ));
+        }
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `grep -rn TODO`
+    pub fn list_annotations(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: Option<String>,
+            tags: Option<Vec<String>>,
+            blame: Option<bool>,
+        }
+        let Arguments { path, tags, blame } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let path = PathBuf::from(path.unwrap_or_else(|| ".".into()));
+        if path.is_absolute() || path_spills_up(&path) {
+            return Err("cannot read files outside the current directory".into());
+        }
+        let tags = tags
+            .filter(|tags| !tags.is_empty())
+            .unwrap_or_else(|| DEFAULT_TAGS.iter().map(|tag| tag.to_string()).collect());
+
+        list_annotations_with_path(&path, &tags, blame.unwrap_or(true))
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tags_are_whole_words() {
+        let tags: Vec<String> = DEFAULT_TAGS.iter().map(|tag| tag.to_string()).collect();
+        assert_eq!(
+            find_tag("// TODO: handle retries", &tags),
+            Some(("TODO".into(), "handle retries"))
+        );
+        assert_eq!(
+            find_tag("/* FIXME(someone) - leaks */", &tags),
+            Some(("FIXME".into(), "(someone) - leaks"))
+        );
+        assert_eq!(find_tag("// TODOS are not tags", &tags), None);
+        assert_eq!(find_tag("// call MY_HACK here", &tags), None);
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn annotations_listing_format() {
+        let tags: Vec<String> = DEFAULT_TAGS.iter().map(|tag| tag.to_string()).collect();
+        let output = list_annotations_with_path(Path::new("."), &tags, true).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
@@ -0,0 +1,81 @@
+//! Reading files and directories as they were at some revision,
+//! straight from the git objects, without checking anything out.
+use std::io;
+use std::path::Path;
+
+use git2::{ObjectType, Repository};
+
+use super::common::path_in_repo;
+
+/// What is found at a path in the tree of a revision.
+pub enum Entry {
+    File(String),
+    Directory(Vec<DirectoryEntry>),
+}
+
+/// One row of `git ls-tree -l`.
+pub struct DirectoryEntry {
+    pub name: String,
+    pub mode: i32,
+    pub kind: Option<ObjectType>,
+    pub size: Option<usize>,
+}
+
+fn other<E: std::fmt::Display>(err: E) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+/// Look up the path, relative to the current directory, in the tree of the revision.
+pub fn entry_at_revision(rev: &str, path: &Path) -> io::Result<Entry> {
+    let repo = Repository::discover(".").map_err(other)?;
+    let tree = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|err| other(format!("cannot resolve `{rev}`: {err}")))?;
+    let relative = path_in_repo(&repo, path)
+        .ok_or_else(|| other(format!("{} is not in the repository", path.display())))?;
+
+    let object = if relative.as_os_str().is_empty() {
+        tree.into_object()
+    } else {
+        tree.get_path(&relative)
+            .and_then(|entry| entry.to_object(&repo))
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} does not exist at `{rev}`", path.display()),
+                )
+            })?
+    };
+
+    if let Some(blob) = object.as_blob() {
+        return Ok(Entry::File(
+            String::from_utf8_lossy(blob.content()).into_owned(),
+        ));
+    }
+    let tree = object.peel_to_tree().map_err(other)?;
+    let entries = tree
+        .iter()
+        .map(|entry| DirectoryEntry {
+            name: entry.name().unwrap_or_default().to_string(),
+            mode: entry.filemode(),
+            kind: entry.kind(),
+            size: entry
+                .to_object(&repo)
+                .ok()
+                .and_then(|object| object.as_blob().map(|blob| blob.size())),
+        })
+        .collect();
+    Ok(Entry::Directory(entries))
+}
+
+/// The contents of the file as it was at the revision.
+pub fn read_at_revision(rev: &str, path: &Path) -> io::Result<String> {
+    match entry_at_revision(rev, path)? {
+        Entry::File(contents) => Ok(contents),
+        Entry::Directory(_) => Err(other(format!(
+            "{} is a directory at `{rev}`",
+            path.display()
+        ))),
+    }
+}
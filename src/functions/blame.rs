@@ -0,0 +1,118 @@
+/* spell-checker:words chrono */
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use git2::{Oid, Repository};
+
+use super::common::{path_in_repo, path_spills_up};
+
+/// `[hash] date author | summary` of the commit, or a note that the lines are new.
+fn describe_commit(repo: &Repository, id: Oid) -> String {
+    if id.is_zero() {
+        return "[uncommitted]".into();
+    }
+    let Ok(commit) = repo.find_commit(id) else {
+        return format!("[{id}]");
+    };
+    let hash = commit
+        .as_object()
+        .short_id()
+        .ok()
+        .and_then(|id| id.as_str().map(str::to_string))
+        .unwrap_or_else(|| id.to_string());
+    let date = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    let author = commit.author();
+    let author = author.name().unwrap_or_default();
+    let summary = commit.summary().unwrap_or_default();
+    format!("[{hash}] {date} {author} | {summary}")
+}
+
+/// `git blame -L start,end path`, grouping the lines by the commit that last touched them.
+pub fn blame_with_path(
+    path: &Path,
+    start: Option<usize>,
+    end: Option<usize>,
+) -> Result<String, String> {
+    if path.is_absolute() || path_spills_up(path) {
+        return Err("cannot read files outside the current directory".into());
+    }
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let relative = path_in_repo(&repo, path).ok_or("the file is not in the repository")?;
+
+    // Blame the file as it is on disk, so that the line numbers match what `F` shows.
+    let contents = std::fs::read(path).map_err(|err| err.to_string())?;
+    let committed = repo
+        .blame_file(&relative, None)
+        .map_err(|err| err.to_string())?;
+    let blame = committed
+        .blame_buffer(&contents)
+        .map_err(|err| err.to_string())?;
+
+    let text = String::from_utf8_lossy(&contents);
+    let lines: Vec<&str> = text.lines().collect();
+    let start = start.unwrap_or(1).max(1);
+    let end = end.unwrap_or(lines.len()).min(lines.len());
+    if start > end {
+        return Err(format!("the file has {} lines", lines.len()));
+    }
+
+    let mut result = String::new();
+    let mut commits = HashMap::new();
+    let mut current_hunk = None;
+    for number in start..=end {
+        let Some(hunk) = blame.get_line(number) else {
+            continue;
+        };
+        let hunk_key = (hunk.final_commit_id(), hunk.final_start_line());
+        if current_hunk != Some(hunk_key) {
+            current_hunk = Some(hunk_key);
+            let header = commits
+                .entry(hunk.final_commit_id())
+                .or_insert_with(|| describe_commit(&repo, hunk.final_commit_id()));
+            result.push_str(header);
+            result.push('\n');
+        }
+        let line = format!("{number:>6} | {}", lines[number - 1]);
+        result.push_str(line.trim_end());
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git blame`
+    pub fn blame(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: String,
+            start_line: Option<usize>,
+            end_line: Option<usize>,
+        }
+        let Arguments {
+            path,
+            start_line,
+            end_line,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        blame_with_path(Path::new(&path), start_line, end_line)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn blame_format() {
+        let output = blame_with_path(Path::new("src/main.rs"), Some(1), Some(20)).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::common::{path_spills_up, walk_files};
+use super::query_ast::{call_sites, enclosing_function, function_spans, parse_source};
+
+/// A function defined somewhere in the codebase.
+struct Definition {
+    name: String,
+    file: PathBuf,
+    start_line: usize,
+    end_line: usize,
+    /// Trait methods and other bare signatures have none.
+    has_body: bool,
+}
+
+/// A call from within a function, or from the top level of a file.
+struct Call {
+    caller: Option<usize>,
+    callee: String,
+    file: PathBuf,
+    line: usize,
+}
+
+/// Which calls which, as far as names can tell.
+struct CallGraph {
+    definitions: Vec<Definition>,
+    calls: Vec<Call>,
+    by_name: HashMap<String, Vec<usize>>,
+}
+
+impl CallGraph {
+    /// Parse every supported file under the given directory.
+    fn of_directory(path: &Path) -> io::Result<Self> {
+        let mut definitions = Vec::new();
+        let mut calls = Vec::new();
+        for file in walk_files(path)? {
+            let ext = file.extension().and_then(|ext| ext.to_str());
+            let Ok(source_code) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            let Some(tree) = parse_source(ext, &source_code) else {
+                continue;
+            };
+
+            let spans = function_spans(ext, &tree, &source_code);
+            let first = definitions.len();
+            for span in &spans {
+                definitions.push(Definition {
+                    name: span.name.clone(),
+                    file: file.clone(),
+                    start_line: span.start_line,
+                    end_line: span.end_line,
+                    has_body: span.body.is_some(),
+                });
+            }
+            for call in call_sites(ext, &tree, &source_code) {
+                let caller = enclosing_function(&spans, call.byte).map(|index| first + index);
+                calls.push(Call {
+                    caller,
+                    callee: call.callee,
+                    file: file.clone(),
+                    line: call.line,
+                });
+            }
+        }
+
+        let mut by_name = HashMap::<String, Vec<usize>>::new();
+        for (index, definition) in definitions.iter().enumerate() {
+            by_name
+                .entry(definition.name.clone())
+                .or_default()
+                .push(index);
+        }
+        // A call resolves to an implementation rather than to a declaration,
+        // so drop the signatures whenever there is something with a body instead.
+        for indices in by_name.values_mut() {
+            if indices.iter().any(|&index| definitions[index].has_body) {
+                indices.retain(|&index| definitions[index].has_body);
+            }
+        }
+        Ok(Self {
+            definitions,
+            calls,
+            by_name,
+        })
+    }
+
+    /// `name @ file:start-end`
+    fn describe(&self, index: usize) -> String {
+        let Definition {
+            name,
+            file,
+            start_line,
+            end_line,
+            ..
+        } = &self.definitions[index];
+        format!("{name} @ {}:{start_line}-{end_line}", file.display())
+    }
+
+    /// Where the definitions with this name are, comma-separated.
+    fn locate(&self, indices: &[usize]) -> String {
+        indices
+            .iter()
+            .map(|&index| {
+                let Definition {
+                    file, start_line, ..
+                } = &self.definitions[index];
+                format!("{}:{start_line}", file.display())
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// What the definitions with this name call, transitively.
+    fn callees(&self, name: &str, depth: usize) -> String {
+        let Some(roots) = self.by_name.get(name) else {
+            return format!("no definition named `{name}`\n");
+        };
+        let mut result = String::new();
+        let mut expanded = HashSet::new();
+        for &root in roots {
+            result.push_str(&self.describe(root));
+            result.push('\n');
+            self.push_callees(root, 1, depth, &mut expanded, &mut result);
+        }
+        result
+    }
+
+    fn push_callees(
+        &self,
+        caller: usize,
+        level: usize,
+        depth: usize,
+        expanded: &mut HashSet<usize>,
+        result: &mut String,
+    ) {
+        if level > depth || !expanded.insert(caller) {
+            return;
+        }
+        let indent = "  ".repeat(level);
+        let mut unresolved = Vec::new();
+        for call in self.calls.iter().filter(|call| call.caller == Some(caller)) {
+            let site = format!("{}:{}", call.file.display(), call.line);
+            match self.by_name.get(&call.callee).map(Vec::as_slice) {
+                None | Some([]) => {
+                    if !unresolved.contains(&call.callee.as_str()) {
+                        unresolved.push(call.callee.as_str());
+                    }
+                }
+                Some(&[callee]) => {
+                    let seen = if expanded.contains(&callee) {
+                        " (see above)"
+                    } else {
+                        ""
+                    };
+                    let callee_description = self.describe(callee);
+                    result.push_str(&format!("{indent}{site} -> {callee_description}{seen}\n"));
+                    self.push_callees(callee, level + 1, depth, expanded, result);
+                }
+                Some(candidates) => {
+                    let locations = self.locate(candidates);
+                    result.push_str(&format!(
+                        "{indent}{site} -> {} (ambiguous: {locations})\n",
+                        call.callee
+                    ));
+                }
+            }
+        }
+        if !unresolved.is_empty() {
+            result.push_str(&format!("{indent}unresolved: {}\n", unresolved.join(", ")));
+        }
+    }
+
+    /// What calls the definitions with this name, transitively.
+    fn callers(&self, name: &str, depth: usize) -> String {
+        let Some(roots) = self.by_name.get(name) else {
+            return format!("no definition named `{name}`\n");
+        };
+        let mut result = String::new();
+        if roots.len() > 1 {
+            let locations = self.locate(roots);
+            result.push_str(&format!(
+                "ambiguous: calls by name cannot tell apart {locations}\n"
+            ));
+        }
+        for &root in roots {
+            result.push_str(&self.describe(root));
+            result.push('\n');
+        }
+        let mut expanded = HashSet::new();
+        self.push_callers(name, 1, depth, &mut expanded, &mut result);
+        result
+    }
+
+    fn push_callers(
+        &self,
+        name: &str,
+        level: usize,
+        depth: usize,
+        expanded: &mut HashSet<String>,
+        result: &mut String,
+    ) {
+        if level > depth || !expanded.insert(name.to_string()) {
+            return;
+        }
+        let indent = "  ".repeat(level);
+        for call in self.calls.iter().filter(|call| call.callee == name) {
+            let site = format!("{}:{}", call.file.display(), call.line);
+            let Some(caller) = call.caller else {
+                result.push_str(&format!("{indent}<- top level (call at {site})\n"));
+                continue;
+            };
+            let caller_name = &self.definitions[caller].name;
+            let seen = if expanded.contains(caller_name) {
+                " (see above)"
+            } else {
+                ""
+            };
+            let caller_description = self.describe(caller);
+            result.push_str(&format!(
+                "{indent}<- {caller_description} (call at {site}){seen}\n"
+            ));
+            self.push_callers(caller_name, level + 1, depth, expanded, result);
+        }
+    }
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `cflow`
+    pub fn call_graph(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Direction {
+            Callees,
+            Callers,
+        }
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            name: String,
+            direction: Direction,
+            depth: Option<usize>,
+            path: Option<String>,
+        }
+        let Arguments {
+            name,
+            direction,
+            depth,
+            path,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let path = PathBuf::from(path.unwrap_or_else(|| ".".into()));
+        if path.is_absolute() || path_spills_up(&path) {
+            return Err("cannot read files outside the current directory".into());
+        }
+        let depth = depth.unwrap_or(2).clamp(1, 10);
+
+        let graph = CallGraph::of_directory(&path).map_err(|err| err.to_string())?;
+        Ok(match direction {
+            Direction::Callees => graph.callees(&name, depth),
+            Direction::Callers => graph.callers(&name, depth),
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn call_graph_format() {
+        let graph = CallGraph::of_directory(Path::new("src")).unwrap();
+        println!("{}", graph.callees("main", 2));
+        println!("{}", graph.callers("show_commit_with_hash", 3));
+        assert!(false);
+    }
+}
@@ -0,0 +1,113 @@
+//! Splitting files into pieces that make sense on their own, for the search indices.
+use serde::{Deserialize, Serialize};
+
+use super::query_ast::{function_spans, parse_source};
+
+/// Glue code between functions, and files we cannot parse, get cut into pieces this long.
+const MAX_LINES_PER_PIECE: usize = 50;
+
+/// A function with its doc comment, or a run of code between functions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// One-based, inclusive.
+    pub start_line: usize,
+    /// One-based, inclusive.
+    pub end_line: usize,
+    /// The function this chunk is, if it is one.
+    pub name: Option<String>,
+}
+
+impl Chunk {
+    /// The lines of the source this chunk spans.
+    pub fn text<'source>(&self, source_code: &'source str) -> &'source str {
+        let mut offsets = source_code
+            .match_indices('\n')
+            .map(|(offset, _)| offset + 1);
+        let start = match self.start_line {
+            1 => 0,
+            line => offsets.nth(line - 2).unwrap_or(source_code.len()),
+        };
+        let mut offsets = source_code[start..]
+            .match_indices('\n')
+            .map(|(offset, _)| start + offset + 1);
+        let end = offsets
+            .nth(self.end_line - self.start_line)
+            .unwrap_or(source_code.len());
+        &source_code[start..end]
+    }
+}
+
+/// True for the lines that belong to whatever definition follows them.
+fn is_preamble(line: &str) -> bool {
+    let line = line.trim_start();
+    ["//", "/*", "*", "#", "@"]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// Cut the lines in `start..=end` into pieces no longer than the limit,
+/// dropping the ones with nothing but whitespace.
+fn push_pieces(lines: &[&str], start: usize, end: usize, result: &mut Vec<Chunk>) {
+    let mut piece_start = start;
+    while piece_start <= end {
+        let piece_end = end.min(piece_start + MAX_LINES_PER_PIECE - 1);
+        let blank = lines[piece_start - 1..piece_end]
+            .iter()
+            .all(|line| line.trim().is_empty());
+        if !blank {
+            result.push(Chunk {
+                start_line: piece_start,
+                end_line: piece_end,
+                name: None,
+            });
+        }
+        piece_start = piece_end + 1;
+    }
+}
+
+/// Split the source along the outermost function boundaries, in order of appearance.
+pub fn chunks_of_source(ext: Option<&str>, source_code: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = source_code.lines().collect();
+    let spans = parse_source(ext, source_code)
+        .map(|tree| function_spans(ext, &tree, source_code))
+        .unwrap_or_default();
+
+    let mut result = Vec::new();
+    let mut next_line = 1;
+    for span in spans {
+        // Nested functions are a part of their enclosing one.
+        if span.start_line < next_line {
+            continue;
+        }
+        let mut start_line = span.start_line;
+        while start_line > next_line && is_preamble(lines[start_line - 2]) {
+            start_line -= 1;
+        }
+        push_pieces(&lines, next_line, start_line - 1, &mut result);
+        result.push(Chunk {
+            start_line,
+            end_line: span.end_line,
+            name: Some(span.name),
+        });
+        next_line = span.end_line + 1;
+    }
+    push_pieces(&lines, next_line, lines.len(), &mut result);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunking() {
+        let source = "use std::io;\n\n/// Doc.\n#[inline]\nfn one() {\n    fn nested() {}\n}\n\nconst X: u8 = 1;\n";
+        let chunks = chunks_of_source(Some("rs"), source);
+        let described: Vec<_> = chunks
+            .iter()
+            .map(|chunk| (chunk.start_line, chunk.end_line, chunk.name.as_deref()))
+            .collect();
+        assert_eq!(described, [(1, 2, None), (3, 7, Some("one")), (8, 9, None)]);
+        assert_eq!(chunks[2].text(source), "\nconst X: u8 = 1;\n");
+    }
+}
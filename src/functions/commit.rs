@@ -0,0 +1,200 @@
+use std::path::Path;
+use std::process::Command;
+
+use git2::{Repository, RepositoryState};
+
+use super::common::{path_in_repo, path_spills_up};
+use super::show_commit::stat_of_diff;
+use crate::io;
+
+/// Let the user change the message in their editor, the way `git commit` would.
+/// Lines starting with `#` are dropped, and so is the whole message if nothing else is left.
+pub fn edit_message(repo: &Repository, message: &str, hint: &str) -> Result<String, String> {
+    let location = repo.path().join("COMMIT_EDITMSG");
+    let mut contents = message.trim_end().to_string();
+    contents.push_str("\n\n");
+    for line in hint.lines() {
+        contents.push_str(format!("# {line}").trim_end());
+        contents.push('\n');
+    }
+    std::fs::write(&location, contents).map_err(|err| err.to_string())?;
+
+    let editor = std::env::var("GIT_EDITOR")
+        .ok()
+        .or_else(|| {
+            repo.config()
+                .and_then(|config| config.get_string("core.editor"))
+                .ok()
+        })
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .unwrap_or_else(|| "vi".into());
+    // The editor setting may carry arguments, so let the shell split it.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg(&editor)
+        .arg(&location)
+        .status()
+        .map_err(|err| format!("cannot run the editor `{editor}`: {err}"))?;
+    if !status.success() {
+        return Err(format!("the editor `{editor}` failed"));
+    }
+
+    let edited = std::fs::read_to_string(&location).map_err(|err| err.to_string())?;
+    let edited: Vec<&str> = edited
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    Ok(edited.join("\n").trim().to_string())
+}
+
+/// Why committing right now might not be what the user wants, if there is a reason.
+fn reason_for_caution(repo: &Repository) -> Option<String> {
+    match repo.state() {
+        RepositoryState::Clean => {}
+        state => return Some(format!("the repository is in the middle of {state:?}")),
+    }
+    if repo.head_detached().unwrap_or(false) {
+        return Some("HEAD is detached, the commit will not be on any branch".into());
+    }
+    None
+}
+
+/// Drop what git keeps for the operation the commit concludes, the way `git commit` would.
+/// A rebase goes on after the commit, so its state is left alone.
+fn conclude(repo: &Repository, state: RepositoryState) -> Result<(), String> {
+    let heads: &[&str] = match state {
+        RepositoryState::Merge => return repo.cleanup_state().map_err(|err| err.to_string()),
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+            &["CHERRY_PICK_HEAD", "MERGE_MSG"]
+        }
+        RepositoryState::Revert | RepositoryState::RevertSequence => &["REVERT_HEAD", "MERGE_MSG"],
+        _ => &[],
+    };
+    for head in heads {
+        match std::fs::remove_file(repo.path().join(head)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.to_string()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `git add paths && git commit -m message`, once the user agrees to it.
+pub fn commit_with_approval(paths: &[String], message: &str) -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+
+    if let Some(reason) = reason_for_caution(&repo) {
+        let answer = io::ask_user(&format!("{reason}. Commit anyway? [y/N]"));
+        if !answer.eq_ignore_ascii_case("y") {
+            return Err(format!("the user declined to commit: {reason}"));
+        }
+    }
+
+    // The paths are staged in memory, and the index is only written once the commit is made.
+    let mut index = repo.index().map_err(|err| err.to_string())?;
+    for path in paths {
+        let path = Path::new(path);
+        if path.is_absolute() || path_spills_up(path) {
+            return Err("cannot stage files outside the current directory".into());
+        }
+        let relative = path_in_repo(&repo, path).ok_or("the path is not in the repository")?;
+        if path.is_dir() {
+            index
+                .add_all([&relative], git2::IndexAddOption::DEFAULT, None)
+                .map_err(|err| err.to_string())?;
+        } else if path.exists() {
+            index.add_path(&relative).map_err(|err| err.to_string())?;
+        } else {
+            index
+                .remove_path(&relative)
+                .map_err(|err| err.to_string())?;
+        }
+    }
+
+    let head = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let head_tree = head.as_ref().and_then(|head| head.tree().ok());
+    let diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), Some(&index), None)
+        .map_err(|err| err.to_string())?;
+    if diff.deltas().len() == 0 {
+        return Err("nothing is staged to commit".into());
+    }
+    let stat = stat_of_diff(&diff)?;
+
+    let mut message = message.trim().to_string();
+    loop {
+        io::show_proposal("This would be committed:", &format!("{stat}\n{message}"));
+        let answer = io::ask_user("Commit with this message? [y]es, [e]dit, [N]o");
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" => break,
+            "e" | "edit" => {
+                let hint = format!(
+                    "Lines starting with '#' are dropped, and an empty message aborts the commit.\n\n{stat}"
+                );
+                message = edit_message(&repo, &message, &hint)?;
+                if message.is_empty() {
+                    return Err("the user emptied the message, so nothing was committed".into());
+                }
+            }
+            _ => {
+                return Err(
+                    "the user declined the commit, and the index was left as it was".into(),
+                );
+            }
+        }
+    }
+
+    let tree_id = index.write_tree().map_err(|err| err.to_string())?;
+    let tree = repo.find_tree(tree_id).map_err(|err| err.to_string())?;
+    let signature = repo.signature().map_err(|err| err.to_string())?;
+    // Finishing a merge makes a merge commit, the way `git commit` would.
+    let state = repo.state();
+    let mut merged = Vec::new();
+    if state == RepositoryState::Merge {
+        let heads = std::fs::read_to_string(repo.path().join("MERGE_HEAD"))
+            .map_err(|err| err.to_string())?;
+        for line in heads.lines().filter(|line| !line.trim().is_empty()) {
+            merged.push(git2::Oid::from_str(line.trim()).map_err(|err| err.to_string())?);
+        }
+    }
+    let merged = merged
+        .into_iter()
+        .map(|id| repo.find_commit(id))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+    let parents: Vec<&git2::Commit> = head.iter().chain(&merged).collect();
+    let id = repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parents,
+        )
+        .map_err(|err| err.to_string())?;
+    index.write().map_err(|err| err.to_string())?;
+    conclude(&repo, state)?;
+
+    let summary = message.lines().next().unwrap_or_default();
+    Ok(format!("committed [{id}] {summary}\n{stat}"))
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git add paths && git commit`, after asking
+    pub fn commit(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            paths: Option<Vec<String>>,
+            message: String,
+        }
+        let Arguments { paths, message } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        commit_with_approval(&paths.unwrap_or_default(), &message)
+    }
+}
@@ -1,4 +1,5 @@
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 
 /// True if the path goes above the current directory.
 pub fn path_spills_up<PathRef: AsRef<Path>>(path: PathRef) -> bool {
@@ -16,7 +17,103 @@ pub fn path_spills_up<PathRef: AsRef<Path>>(path: PathRef) -> bool {
     false
 }
 
+/// Every file under the given directory worth looking into,
+/// skipping hidden entries and whatever the repository ignores.
+pub fn walk_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let repo = git2::Repository::discover(".").ok();
+    let workdir = repo
+        .as_ref()
+        .and_then(|repo| repo.workdir())
+        .and_then(|workdir| workdir.canonicalize().ok());
+    let is_ignored = |path: &Path| -> bool {
+        let (Some(repo), Some(workdir)) = (repo.as_ref(), workdir.as_ref()) else {
+            return false;
+        };
+        let Ok(path) = path.canonicalize() else {
+            return false;
+        };
+        let Ok(relative) = path.strip_prefix(workdir) else {
+            return false;
+        };
+        repo.is_path_ignored(relative).unwrap_or(false)
+    };
+
+    let mut result = Vec::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        for entry in directory.read_dir()? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            let path = path.strip_prefix(".").unwrap_or(&path).to_path_buf();
+            if entry.file_name().to_string_lossy().starts_with('.') || is_ignored(&path) {
+                continue;
+            }
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                result.push(path);
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// The path as the repository sees it: relative to its working directory.
+pub fn path_in_repo(repo: &git2::Repository, path: &Path) -> Option<PathBuf> {
+    let workdir = repo.workdir()?.canonicalize().ok()?;
+    // The path may not exist on disk, when it is looked up in another revision.
+    let path = std::env::current_dir()
+        .ok()?
+        .canonicalize()
+        .ok()?
+        .join(path);
+    let path = path.canonicalize().unwrap_or(path);
+    path.strip_prefix(workdir).ok().map(Path::to_path_buf)
+}
+
+/// Turn an optional path relative to the current directory into a pathspec for git.
+pub fn pathspec_in_repo(
+    repo: &git2::Repository,
+    path: Option<&str>,
+) -> Result<Option<String>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let path = Path::new(path);
+    if path.is_absolute() || path_spills_up(path) {
+        return Err("cannot read files outside the current directory".into());
+    }
+    let relative = path_in_repo(repo, path).ok_or("the path is not in the repository")?;
+    Ok(Some(relative.to_string_lossy().into_owned()))
+}
+
+/// A hash of the bytes that stays the same across runs and builds,
+/// to tell whether a file changed since it was last looked at.
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    // FNV-1a.
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Where to keep what we compute about the codebase between runs:
+/// inside `.git` if there is one, so that it never gets committed.
+/// The name is made distinct per working directory, since the paths inside are relative to it.
+pub fn cache_file(name: &str) -> io::Result<PathBuf> {
+    let directory = match git2::Repository::discover(".") {
+        Ok(repo) => repo.path().join("well"),
+        Err(_) => PathBuf::from(".well"),
+    };
+    std::fs::create_dir_all(&directory)?;
+    let current_dir = std::env::current_dir()?;
+    let scope = content_hash(current_dir.as_os_str().as_encoded_bytes());
+    Ok(directory.join(format!("{name}-{scope:016x}.json")))
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use super::*;
 
@@ -0,0 +1,532 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use git2::{DiffOptions, Oid, Repository};
+
+use super::common::{path_in_repo, path_spills_up};
+use super::working_tree::conflict_kind;
+use crate::io;
+
+/// How many commits on each side to look through for the ones that touched a conflict.
+const MAX_COMMITS_SCANNED: usize = 200;
+
+/// How many commits on each side to show for a conflict.
+const MAX_COMMITS_SHOWN: usize = 5;
+
+/// The heads git leaves for the other side, by what is in progress.
+const OTHER_HEADS: &[&str] = &[
+    "MERGE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REBASE_HEAD",
+    "REVERT_HEAD",
+];
+
+/// One `<<<<<<<` to `>>>>>>>` region of a conflicted file.
+/// The file is kept as bytes, since it need not be UTF-8 and is written back as it was.
+struct Conflict<'a> {
+    /// The region as it is in the file, markers included.
+    raw: &'a [u8],
+    start_line: usize,
+    end_line: usize,
+    ours_label: String,
+    theirs_label: String,
+    ours: Vec<u8>,
+    /// Only there when the file was written with `merge.conflictStyle = diff3` or `zdiff3`.
+    base: Option<Vec<u8>>,
+    theirs: Vec<u8>,
+}
+
+enum Piece<'a> {
+    Text(&'a [u8]),
+    Conflict(Conflict<'a>),
+}
+
+/// Split the file into the text around the conflicts and the conflicts themselves.
+/// A region without its closing marker is left as text.
+fn parse_conflicts(text: &[u8]) -> Vec<Piece<'_>> {
+    #[derive(PartialEq)]
+    enum Side {
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut pieces = Vec::new();
+    let mut text_start = 0;
+    let mut offset = 0;
+    // The conflict being read, where it starts, and which side of it the lines are on.
+    let mut open: Option<(Conflict, usize, Side)> = None;
+    for (index, line) in text.split_inclusive(|&byte| byte == b'\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+        let marker = |prefix: &str| {
+            line.strip_prefix(prefix.as_bytes())
+                .filter(|rest| rest.first().is_none_or(|byte| b" \r\n".contains(byte)))
+                .map(|rest| String::from_utf8_lossy(rest).trim().to_string())
+        };
+        let Some((conflict, start, side)) = open.as_mut() else {
+            if let Some(label) = marker("<<<<<<<") {
+                let conflict = Conflict {
+                    raw: b"",
+                    start_line: index + 1,
+                    end_line: index + 1,
+                    ours_label: label,
+                    theirs_label: String::new(),
+                    ours: Vec::new(),
+                    base: None,
+                    theirs: Vec::new(),
+                };
+                open = Some((conflict, line_start, Side::Ours));
+            }
+            continue;
+        };
+        if *side == Side::Ours && marker("|||||||").is_some() {
+            *side = Side::Base;
+            conflict.base = Some(Vec::new());
+        } else if *side != Side::Theirs && marker("=======").is_some() {
+            *side = Side::Theirs;
+        } else if let Some(label) = marker(">>>>>>>").filter(|_| *side == Side::Theirs) {
+            conflict.raw = &text[*start..offset];
+            conflict.end_line = index + 1;
+            conflict.theirs_label = label;
+            if text_start < *start {
+                pieces.push(Piece::Text(&text[text_start..*start]));
+            }
+            text_start = offset;
+            if let Some((conflict, _, _)) = open.take() {
+                pieces.push(Piece::Conflict(conflict));
+            }
+        } else {
+            match side {
+                Side::Ours => conflict.ours.extend_from_slice(line),
+                Side::Base => conflict
+                    .base
+                    .get_or_insert_with(Vec::new)
+                    .extend_from_slice(line),
+                Side::Theirs => conflict.theirs.extend_from_slice(line),
+            }
+        }
+    }
+    if text_start < text.len() {
+        pieces.push(Piece::Text(&text[text_start..]));
+    }
+    pieces
+}
+
+/// The conflicted files in the index that are under the current directory,
+/// relative to the repository and to the current directory, with the kind of conflict.
+fn conflicted_files(repo: &Repository) -> Result<Vec<(PathBuf, PathBuf, &'static str)>, String> {
+    let here = path_in_repo(repo, Path::new(".")).unwrap_or_default();
+    let index = repo.index().map_err(|err| err.to_string())?;
+    let mut files = Vec::new();
+    for conflict in index.conflicts().map_err(|err| err.to_string())? {
+        let conflict = conflict.map_err(|err| err.to_string())?;
+        let kind = conflict_kind(&conflict);
+        let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) else {
+            continue;
+        };
+        let relative = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+        if let Ok(local) = relative.strip_prefix(&here) {
+            files.push((relative.clone(), local.to_path_buf(), kind));
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// A commit on one side, with the lines it added to the file.
+struct SideCommit {
+    id: Oid,
+    summary: String,
+    added: HashSet<String>,
+}
+
+/// The commits between the base and the tip that changed the file, the newest first.
+fn commits_on_side(repo: &Repository, base: Oid, tip: Oid, path: &Path) -> Vec<SideCommit> {
+    let Ok(mut revwalk) = repo.revwalk() else {
+        return Vec::new();
+    };
+    if revwalk.push(tip).is_err() || revwalk.hide(base).is_err() {
+        return Vec::new();
+    }
+    let mut result = Vec::new();
+    for id in revwalk.filter_map(Result::ok).take(MAX_COMMITS_SCANNED) {
+        let Ok(commit) = repo.find_commit(id) else {
+            continue;
+        };
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+        let tree = commit.tree().ok();
+        let mut options = DiffOptions::new();
+        options.pathspec(path);
+        let Ok(diff) =
+            repo.diff_tree_to_tree(parent_tree.as_ref(), tree.as_ref(), Some(&mut options))
+        else {
+            continue;
+        };
+        let mut added = HashSet::new();
+        diff.foreach(
+            &mut |_, _| true,
+            None,
+            None,
+            Some(&mut |_, _, line| {
+                if line.origin() == '+' {
+                    let content = String::from_utf8_lossy(line.content()).trim().to_string();
+                    if !content.is_empty() {
+                        added.insert(content);
+                    }
+                }
+                true
+            }),
+        )
+        .ok();
+        if !added.is_empty() {
+            result.push(SideCommit {
+                id,
+                summary: commit.summary().unwrap_or_default().to_string(),
+                added,
+            });
+        }
+    }
+    result
+}
+
+/// The commits that added any of the lines of the side of a conflict.
+fn commits_touching(commits: &[SideCommit], side: &[u8]) -> Vec<String> {
+    let side = String::from_utf8_lossy(side);
+    let lines: HashSet<&str> = side
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    commits
+        .iter()
+        .filter(|commit| {
+            commit
+                .added
+                .iter()
+                .any(|line| lines.contains(line.as_str()))
+        })
+        .take(MAX_COMMITS_SHOWN)
+        .map(|commit| format!("[{}] {}", &commit.id.to_string()[..7], commit.summary))
+        .collect()
+}
+
+/// Indent each line of the text, so the conflicts stand out of the listing.
+fn indented(text: &[u8]) -> String {
+    let text = String::from_utf8_lossy(text);
+    let mut result = String::new();
+    for line in text.lines() {
+        result.push_str(&format!("    | {line}\n"));
+    }
+    if text.is_empty() {
+        result.push_str("    (nothing)\n");
+    }
+    result
+}
+
+/// Every conflict in the working tree, with each side, and the commits on either side behind it.
+pub fn list_conflicts() -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let files = conflicted_files(&repo)?;
+    if files.is_empty() {
+        return Ok("no conflicts\n".into());
+    }
+
+    let head = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let other = OTHER_HEADS.iter().find_map(|name| {
+        repo.revparse_single(name)
+            .and_then(|object| object.peel_to_commit())
+            .ok()
+    });
+    let sides = match (&head, &other) {
+        (Some(head), Some(other)) => repo
+            .merge_base(head.id(), other.id())
+            .ok()
+            .map(|base| (base, head.id(), other.id())),
+        _ => None,
+    };
+
+    let mut result = format!("In progress: {:?}\n", repo.state());
+    for (relative, local, kind) in files {
+        // Binary files, with a NUL byte as git tells them, and missing ones have no markers,
+        // so the user has to choose a side of the whole file.
+        let text = match std::fs::read(&local) {
+            Ok(bytes) if !bytes.contains(&0) => bytes,
+            Ok(_) => {
+                result.push_str(&format!("\n{} ({kind}, binary)\n", local.display()));
+                continue;
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                result.push_str(&format!(
+                    "\n{} ({kind}, not in the working tree)\n",
+                    local.display()
+                ));
+                continue;
+            }
+            Err(err) => {
+                result.push_str(&format!("\n{} ({kind}, {err})\n", local.display()));
+                continue;
+            }
+        };
+        let pieces = parse_conflicts(&text);
+        let conflicts: Vec<&Conflict> = pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Conflict(conflict) => Some(conflict),
+                Piece::Text(_) => None,
+            })
+            .collect();
+        result.push_str(&format!(
+            "\n{} ({kind}, {} conflicts)\n",
+            local.display(),
+            conflicts.len()
+        ));
+        if conflicts.is_empty() {
+            result.push_str(
+                "    no markers left in the file, mark it resolved with `X` once it is right\n",
+            );
+            continue;
+        }
+        let (ours_commits, theirs_commits) = match sides {
+            Some((base, ours, theirs)) => (
+                commits_on_side(&repo, base, ours, &relative),
+                commits_on_side(&repo, base, theirs, &relative),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        for (number, conflict) in conflicts.iter().enumerate() {
+            result.push_str(&format!(
+                "\nConflict {}, lines {}-{}:\n",
+                number + 1,
+                conflict.start_line,
+                conflict.end_line
+            ));
+            result.push_str(&format!("  ours ({}):\n", conflict.ours_label));
+            result.push_str(&indented(&conflict.ours));
+            if let Some(base) = &conflict.base {
+                result.push_str("  base:\n");
+                result.push_str(&indented(base));
+            }
+            result.push_str(&format!("  theirs ({}):\n", conflict.theirs_label));
+            result.push_str(&indented(&conflict.theirs));
+            for (title, commits, side) in [
+                ("ours", &ours_commits, &conflict.ours),
+                ("theirs", &theirs_commits, &conflict.theirs),
+            ] {
+                let touching = commits_touching(commits, side);
+                if !touching.is_empty() {
+                    result.push_str(&format!("  commits on {title}:\n"));
+                    for commit in touching {
+                        result.push_str(&format!("    {commit}\n"));
+                    }
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Replace the conflicts the user accepts the proposed text for,
+/// and mark the file resolved once no conflict is left in it.
+pub fn resolve_conflicts(path: &Path, resolutions: &[(usize, String)]) -> Result<String, String> {
+    if path.is_absolute() || path_spills_up(path) {
+        return Err("cannot write files outside the current directory".into());
+    }
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let relative = path_in_repo(&repo, path).ok_or("the file is not in the repository")?;
+    let Some(kind) = conflicted_files(&repo)?
+        .into_iter()
+        .find_map(|(conflicted, _, kind)| (conflicted == relative).then_some(kind))
+    else {
+        return Err(format!("`{}` is not conflicted", path.display()));
+    };
+
+    // A file one side deleted may not be there, and then it has no conflicts to resolve either.
+    let exists = path.exists();
+    // Binary files have no markers either, and are only written back with conflicts resolved,
+    // byte for byte around the replaced regions.
+    let text = if exists {
+        std::fs::read(path).map_err(|err| err.to_string())?
+    } else {
+        Vec::new()
+    };
+    let pieces = parse_conflicts(&text);
+    let total = pieces
+        .iter()
+        .filter(|piece| matches!(piece, Piece::Conflict(_)))
+        .count();
+    if let Some((number, _)) = resolutions
+        .iter()
+        .find(|(number, _)| *number == 0 || *number > total)
+    {
+        return Err(format!(
+            "there is no conflict {number}, the file has {total} of them"
+        ));
+    }
+
+    let mut result = Vec::new();
+    let mut accepted = 0;
+    let mut number = 0;
+    for piece in &pieces {
+        let conflict = match piece {
+            Piece::Text(text) => {
+                result.extend_from_slice(text);
+                continue;
+            }
+            Piece::Conflict(conflict) => conflict,
+        };
+        number += 1;
+        let Some((_, proposal)) = resolutions.iter().find(|(each, _)| *each == number) else {
+            result.extend_from_slice(conflict.raw);
+            continue;
+        };
+        io::show_proposal(
+            &format!(
+                "{} conflict {number}, lines {}-{}, would become:",
+                path.display(),
+                conflict.start_line,
+                conflict.end_line
+            ),
+            &indented(proposal.as_bytes()),
+        );
+        let answer = io::ask_user(&format!("Take this for conflict {number}? [y/N]"));
+        if answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") {
+            result.extend_from_slice(proposal.as_bytes());
+            if !proposal.is_empty() && !proposal.ends_with('\n') {
+                result.push(b'\n');
+            }
+            accepted += 1;
+        } else {
+            result.extend_from_slice(conflict.raw);
+        }
+    }
+
+    if accepted > 0 {
+        std::fs::write(path, &result).map_err(|err| err.to_string())?;
+    }
+    let left = total - accepted;
+    if left > 0 {
+        return Ok(format!(
+            "the user took {accepted} of the {} proposed resolutions, {left} of {total} conflicts are left in `{}`",
+            resolutions.len(),
+            path.display()
+        ));
+    }
+    // Nothing was there to resolve, so whether the file is right as it is, is up to the user.
+    if total == 0 {
+        let state = if exists { "as it is" } else { "deleted" };
+        let answer = io::ask_user(&format!(
+            "`{}` ({kind}) has no conflict markers. Mark it resolved {state}? [y/N]",
+            path.display()
+        ));
+        if !(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")) {
+            return Ok(format!(
+                "the user left `{}` ({kind}) unresolved",
+                path.display()
+            ));
+        }
+    }
+    let mut index = repo.index().map_err(|err| err.to_string())?;
+    if exists {
+        index.add_path(&relative)
+    } else {
+        index.remove_path(&relative)
+    }
+    .map_err(|err| err.to_string())?;
+    index.write().map_err(|err| err.to_string())?;
+    if total == 0 {
+        return Ok(format!("the user marked `{}` resolved", path.display()));
+    }
+    Ok(format!(
+        "resolved all {total} conflicts in `{}` and marked it resolved",
+        path.display()
+    ))
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git diff --name-only --diff-filter=U`, with each conflict picked apart
+    pub fn list_conflicts(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {}
+        let Arguments {} = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        super::list_conflicts()
+    }
+
+    /// Edit out the markers, then `git add path`, after asking
+    pub fn resolve_conflicts(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Resolution {
+            conflict: usize,
+            text: String,
+        }
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: String,
+            resolutions: Vec<Resolution>,
+        }
+        let Arguments { path, resolutions } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let resolutions: Vec<(usize, String)> = resolutions
+            .into_iter()
+            .map(|resolution| (resolution.conflict, resolution.text))
+            .collect();
+        super::resolve_conflicts(Path::new(&path), &resolutions)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conflict_regions() {
+        let text = b"a\n<<<<<<< HEAD\nb\n||||||| base\nc\n=======\nd\ne\n>>>>>>> feature\nf\xe9\n<<<<<<< HEAD\n=======\ng\n>>>>>>> feature\n";
+        let pieces = parse_conflicts(text);
+        assert_eq!(pieces.len(), 4);
+        let Piece::Conflict(first) = &pieces[1] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!((first.start_line, first.end_line), (2, 9));
+        assert_eq!(
+            (&*first.ours_label, &*first.theirs_label),
+            ("HEAD", "feature")
+        );
+        assert_eq!(first.ours, b"b\n");
+        assert_eq!(first.base.as_deref(), Some(&b"c\n"[..]));
+        assert_eq!(first.theirs, b"d\ne\n");
+        let Piece::Conflict(second) = &pieces[3] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!((&*second.ours, second.base.as_deref()), (&b""[..], None));
+
+        // Bytes that are not UTF-8, like the Latin-1 `é` above, come back as they were.
+        let rebuilt: Vec<u8> = pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => *text,
+                Piece::Conflict(conflict) => conflict.raw,
+            })
+            .collect::<Vec<_>>()
+            .concat();
+        assert_eq!(rebuilt, text);
+
+        // Without the closing marker, it is only text.
+        assert_eq!(parse_conflicts(b"<<<<<<< HEAD\nb\n=======\n").len(), 1);
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn conflicts_format() {
+        println!("{}", list_conflicts().unwrap());
+        assert!(false);
+    }
+}
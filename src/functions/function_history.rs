@@ -0,0 +1,226 @@
+/* spell-checker:words chrono revwalk */
+
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, Delta, DiffFindOptions, DiffOptions, Oid, Patch, Repository, Sort};
+
+use super::common::{path_in_repo, path_spills_up};
+use super::query_ast::{function_spans, parse_source};
+
+/// The id of the file as it was in the commit, if it was there.
+fn blob_at(commit: &Commit, path: &Path) -> Option<Oid> {
+    Some(commit.tree().ok()?.get_path(path).ok()?.id())
+}
+
+/// The text of the named function in the file as it was in the commit, if it was there.
+fn function_at(repo: &Repository, commit: &Commit, path: &Path, name: &str) -> Option<String> {
+    let entry = commit.tree().ok()?.get_path(path).ok()?;
+    let blob = entry.to_object(repo).ok()?.peel_to_blob().ok()?;
+    let source_code = String::from_utf8_lossy(blob.content());
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    let tree = parse_source(ext, &source_code)?;
+    let spans = function_spans(ext, &tree, &source_code);
+    // Prefer the definition with a body over a mere declaration.
+    let span = spans
+        .iter()
+        .filter(|span| span.name == name)
+        .max_by_key(|span| span.body.is_some())?;
+    Some(source_code[span.range.clone()].to_string())
+}
+
+/// Where the file was in the parent, if the commit renamed it.
+fn path_before(repo: &Repository, commit: &Commit, parent: &Commit, path: &Path) -> PathBuf {
+    let (Ok(old_tree), Ok(new_tree)) = (parent.tree(), commit.tree()) else {
+        return path.to_path_buf();
+    };
+    if old_tree.get_path(path).is_ok() {
+        return path.to_path_buf();
+    }
+    let mut diff = match repo.diff_tree_to_tree(
+        Some(&old_tree),
+        Some(&new_tree),
+        Some(&mut DiffOptions::new()),
+    ) {
+        Ok(diff) => diff,
+        Err(_) => return path.to_path_buf(),
+    };
+    if diff
+        .find_similar(Some(DiffFindOptions::new().renames(true)))
+        .is_err()
+    {
+        return path.to_path_buf();
+    }
+    diff.deltas()
+        .filter(|delta| delta.status() == Delta::Renamed)
+        .find(|delta| delta.new_file().path() == Some(path))
+        .and_then(|delta| delta.old_file().path().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn describe_commit(commit: &Commit) -> String {
+    let short = commit
+        .as_object()
+        .short_id()
+        .ok()
+        .and_then(|id| id.as_str().map(str::to_string))
+        .unwrap_or_else(|| commit.id().to_string());
+    let date = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    let author = commit.author();
+    let author = author.name().unwrap_or_default();
+    let summary = commit.summary().unwrap_or_default();
+    format!("[{short}] {date} {author} | {summary}")
+}
+
+/// `git log -L :name:path`, but finding the function with tree-sitter in every revision.
+pub fn history_of_function(
+    path: &Path,
+    name: &str,
+    rev: Option<&str>,
+    max_count: usize,
+) -> Result<String, String> {
+    if path.is_absolute() || path_spills_up(path) {
+        return Err("cannot read files outside the current directory".into());
+    }
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let mut path = path_in_repo(&repo, path).ok_or("the file is not in the repository")?;
+
+    let start = repo
+        .revparse_single(rev.unwrap_or("HEAD"))
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|err| err.to_string())?;
+    let Some(mut current) = function_at(&repo, &start, &path, name) else {
+        return Err(format!(
+            "no function `{name}` in {} at {}",
+            path.display(),
+            rev.unwrap_or("HEAD")
+        ));
+    };
+
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+        .map_err(|err| err.to_string())?;
+    revwalk.push(start.id()).map_err(|err| err.to_string())?;
+    revwalk
+        .simplify_first_parent()
+        .map_err(|err| err.to_string())?;
+
+    let mut result = String::new();
+    let mut found = 0;
+    for id in revwalk {
+        let id = id.map_err(|err| err.to_string())?;
+        let commit = repo.find_commit(id).map_err(|err| err.to_string())?;
+        let parent = commit.parent(0).ok();
+        // Most commits leave the file alone, and there is no need to parse it for those.
+        if let Some(parent) = &parent {
+            let blob = blob_at(&commit, &path);
+            if blob.is_some() && blob == blob_at(parent, &path) {
+                continue;
+            }
+        }
+        let old_path = match &parent {
+            Some(parent) => path_before(&repo, &commit, parent, &path),
+            None => path.clone(),
+        };
+        let previous = parent
+            .as_ref()
+            .and_then(|parent| function_at(&repo, parent, &old_path, name));
+        if previous.as_deref() == Some(current.as_str()) {
+            path = old_path;
+            continue;
+        }
+
+        found += 1;
+        if found > max_count {
+            result.push_str("older changes are not shown, pass a revision to start from\n");
+            break;
+        }
+        result.push_str(&describe_commit(&commit));
+        result.push('\n');
+        if old_path != path {
+            result.push_str(&format!(
+                "renamed {} -> {}\n",
+                old_path.display(),
+                path.display()
+            ));
+        }
+        // Definitions stop short of the newline, which the patch would otherwise point out.
+        let old_text = previous
+            .as_ref()
+            .map(|text| format!("{text}\n"))
+            .unwrap_or_default();
+        let new_text = format!("{current}\n");
+        let patch = Patch::from_buffers(
+            old_text.as_bytes(),
+            Some(&old_path),
+            new_text.as_bytes(),
+            Some(&path),
+            None,
+        )
+        .and_then(|mut patch| patch.to_buf())
+        .map_err(|err| err.to_string())?;
+        // The file headers only repeat the path, so go straight to the hunks.
+        let patch = String::from_utf8_lossy(&patch);
+        for line in patch.lines().skip_while(|line| !line.starts_with("@@")) {
+            result.push_str(line);
+            result.push('\n');
+        }
+        result.push('\n');
+
+        match previous {
+            Some(previous) => current = previous,
+            None => {
+                result.push_str(&format!("`{name}` first appeared in this commit\n"));
+                break;
+            }
+        }
+        path = old_path;
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git log -L :name:path`
+    pub fn function_history(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: String,
+            name: String,
+            rev: Option<String>,
+            max_count: Option<usize>,
+        }
+        let Arguments {
+            path,
+            name,
+            rev,
+            max_count,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let max_count = max_count.unwrap_or(10).clamp(1, 100);
+        history_of_function(Path::new(&path), &name, rev.as_deref(), max_count)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn function_history_format() {
+        let output = history_of_function(
+            Path::new("src/functions/common.rs"),
+            "path_in_repo",
+            None,
+            5,
+        )
+        .unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
@@ -0,0 +1,342 @@
+/* spell-checker:words chrono revwalk */
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, DiffOptions, Oid, Patch, Repository, Sort};
+use tree_sitter::Node;
+
+use super::common::pathspec_in_repo;
+use super::query_ast::{function_spans, parse_source};
+
+/// How many of the riskiest files get their functions looked into as well.
+const FILES_WITH_FUNCTIONS: usize = 10;
+
+/// How many functions to show under each file.
+const FUNCTIONS_PER_FILE: usize = 3;
+
+/// How often a file changed, and by how many people.
+#[derive(Default)]
+struct Churn {
+    commits: Vec<Oid>,
+    authors: HashSet<String>,
+}
+
+struct FunctionHotspot {
+    name: String,
+    start_line: usize,
+    changes: usize,
+    complexity: usize,
+}
+
+struct FileHotspot {
+    path: PathBuf,
+    changes: usize,
+    authors: usize,
+    complexity: usize,
+    functions: Vec<FunctionHotspot>,
+}
+
+impl FileHotspot {
+    fn score(&self) -> usize {
+        self.changes * self.complexity
+    }
+}
+
+/// True for the nodes that add a way through the code: branches, loops, and short circuits.
+fn is_decision(node: Node, source_code: &[u8]) -> bool {
+    match node.kind() {
+        "if_statement"
+        | "if_expression"
+        | "elif_clause"
+        | "for_statement"
+        | "for_expression"
+        | "for_in_statement"
+        | "for_in_clause"
+        | "while_statement"
+        | "while_expression"
+        | "do_statement"
+        | "loop_expression"
+        | "match_arm"
+        | "case_clause"
+        | "switch_case"
+        | "except_clause"
+        | "catch_clause"
+        | "conditional_expression"
+        | "ternary_expression"
+        | "boolean_operator" => true,
+        "binary_expression" => node
+            .child_by_field_name("operator")
+            .and_then(|operator| operator.utf8_text(source_code).ok())
+            .is_some_and(|operator| matches!(operator, "&&" | "||" | "??")),
+        _ => false,
+    }
+}
+
+/// Cyclomatic complexity, roughly: one plus the number of decisions within the node.
+fn complexity(node: Node, source_code: &[u8]) -> usize {
+    let mut count = 1;
+    let mut cursor = node.walk();
+    let mut descending = true;
+    loop {
+        if descending && is_decision(cursor.node(), source_code) {
+            count += 1;
+        }
+        if descending && cursor.goto_first_child() {
+            continue;
+        }
+        if cursor.goto_next_sibling() {
+            descending = true;
+            continue;
+        }
+        if !cursor.goto_parent() || cursor.node() == node {
+            break;
+        }
+        descending = false;
+    }
+    count
+}
+
+/// The source of the file at the commit, if it is there and reads as text.
+fn source_at(repo: &Repository, commit: &Commit, path: &Path) -> Option<String> {
+    let entry = commit.tree().ok()?.get_path(path).ok()?;
+    let blob = entry.to_object(repo).ok()?.peel_to_blob().ok()?;
+    (!blob.is_binary()).then(|| String::from_utf8_lossy(blob.content()).into_owned())
+}
+
+/// Lines on the new side of the diff that the commit touched in the file.
+fn touched_lines(repo: &Repository, commit: &Commit, path: &Path) -> Vec<(usize, usize)> {
+    let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+    let Ok(tree) = commit.tree() else {
+        return Vec::new();
+    };
+    let mut options = DiffOptions::new();
+    options.pathspec(path).context_lines(0);
+    let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+    else {
+        return Vec::new();
+    };
+    let mut result = Vec::new();
+    for index in 0..diff.deltas().len() {
+        let Ok(Some(patch)) = Patch::from_diff(&diff, index) else {
+            continue;
+        };
+        for hunk_index in 0..patch.num_hunks() {
+            if let Ok((hunk, _)) = patch.hunk(hunk_index) {
+                let start = hunk.new_start() as usize;
+                // A pure deletion still happened at that spot.
+                let end = start + (hunk.new_lines() as usize).max(1) - 1;
+                result.push((start, end));
+            }
+        }
+    }
+    result
+}
+
+/// Which functions changed how often, over the commits that touched the file.
+fn function_churn(repo: &Repository, path: &Path, commits: &[Oid]) -> HashMap<String, usize> {
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    let mut result = HashMap::new();
+    for &id in commits {
+        let Ok(commit) = repo.find_commit(id) else {
+            continue;
+        };
+        let Some(source_code) = source_at(repo, &commit, path) else {
+            continue;
+        };
+        let Some(tree) = parse_source(ext, &source_code) else {
+            continue;
+        };
+        let touched = touched_lines(repo, &commit, path);
+        let changed: HashSet<String> = function_spans(ext, &tree, &source_code)
+            .into_iter()
+            .filter(|span| {
+                touched
+                    .iter()
+                    .any(|&(start, end)| start <= span.end_line && span.start_line <= end)
+            })
+            .map(|span| span.name)
+            .collect();
+        for name in changed {
+            *result.entry(name).or_default() += 1;
+        }
+    }
+    result
+}
+
+/// Rank the files, and the functions within the riskiest of them,
+/// by how often they changed times how complex they are now.
+pub fn hotspots_report(path: Option<&str>, days: u32, limit: usize) -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let pathspec = pathspec_in_repo(&repo, path)?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|err| err.to_string())?;
+    let since = chrono::Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TIME)
+        .map_err(|err| err.to_string())?;
+    revwalk.push(head.id()).map_err(|err| err.to_string())?;
+
+    let mut churn: HashMap<PathBuf, Churn> = HashMap::new();
+    let mut scanned = 0;
+    for id in revwalk {
+        let id = id.map_err(|err| err.to_string())?;
+        let commit = repo.find_commit(id).map_err(|err| err.to_string())?;
+        if commit.time().seconds() < since {
+            break;
+        }
+        // Merges repeat what their branches did.
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        scanned += 1;
+        let tree = commit.tree().map_err(|err| err.to_string())?;
+        let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+        let mut options = DiffOptions::new();
+        if let Some(pathspec) = &pathspec {
+            options.pathspec(pathspec);
+        }
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+            .map_err(|err| err.to_string())?;
+        let author = commit.author();
+        let author = author.email().or(author.name()).unwrap_or_default();
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path() else {
+                continue;
+            };
+            let entry = churn.entry(path.to_path_buf()).or_default();
+            entry.commits.push(id);
+            entry.authors.insert(author.to_string());
+        }
+    }
+
+    let mut files: Vec<FileHotspot> = churn
+        .iter()
+        .filter_map(|(path, churn)| {
+            let source_code = source_at(&repo, &head, path)?;
+            let ext = path.extension().and_then(|ext| ext.to_str());
+            let tree = parse_source(ext, &source_code)?;
+            Some(FileHotspot {
+                path: path.clone(),
+                changes: churn.commits.len(),
+                authors: churn.authors.len(),
+                complexity: complexity(tree.root_node(), source_code.as_bytes()),
+                functions: Vec::new(),
+            })
+        })
+        .collect();
+    files.sort_by(|one, another| {
+        another
+            .score()
+            .cmp(&one.score())
+            .then_with(|| one.path.cmp(&another.path))
+    });
+    files.truncate(limit);
+
+    for file in files.iter_mut().take(FILES_WITH_FUNCTIONS) {
+        let Some(source_code) = source_at(&repo, &head, &file.path) else {
+            continue;
+        };
+        let ext = file.path.extension().and_then(|ext| ext.to_str());
+        let Some(tree) = parse_source(ext, &source_code) else {
+            continue;
+        };
+        let changes = function_churn(&repo, &file.path, &churn[&file.path].commits);
+        let mut functions: Vec<FunctionHotspot> = function_spans(ext, &tree, &source_code)
+            .into_iter()
+            .filter_map(|span| {
+                let changes = *changes.get(&span.name)?;
+                let node = tree
+                    .root_node()
+                    .descendant_for_byte_range(span.range.start, span.range.end)?;
+                Some(FunctionHotspot {
+                    complexity: complexity(node, source_code.as_bytes()),
+                    name: span.name,
+                    start_line: span.start_line,
+                    changes,
+                })
+            })
+            .collect();
+        functions.sort_by_key(|function| std::cmp::Reverse(function.changes * function.complexity));
+        functions.truncate(FUNCTIONS_PER_FILE);
+        file.functions = functions;
+    }
+
+    let mut result = format!("{scanned} commits in the last {days} days\n");
+    if files.is_empty() {
+        result.push_str("no source files changed\n");
+        return Ok(result);
+    }
+    result.push_str(" score changes authors complexity file\n");
+    for file in &files {
+        result.push_str(&format!(
+            "{:>6} {:>7} {:>7} {:>10} {}\n",
+            file.score(),
+            file.changes,
+            file.authors,
+            file.complexity,
+            file.path.display()
+        ));
+        for function in &file.functions {
+            result.push_str(&format!(
+                "{:>6} {:>7} {:>7} {:>10}   `{}` at line {}\n",
+                function.changes * function.complexity,
+                function.changes,
+                "",
+                function.complexity,
+                function.name,
+                function.start_line
+            ));
+        }
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git log --stat | sort | uniq -c`, weighed by complexity
+    pub fn hotspots(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: Option<String>,
+            days: Option<u32>,
+            limit: Option<usize>,
+        }
+        let Arguments { path, days, limit } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let limit = limit.unwrap_or(15).clamp(1, 100);
+        hotspots_report(path.as_deref(), days.unwrap_or(365), limit)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cyclomatic_complexity() {
+        let source = "fn f(x: bool, y: bool) {\n    if x && y {\n        for _ in 0..1 {}\n    } else if y {\n    }\n}\n";
+        let tree = parse_source(Some("rs"), source).unwrap();
+        assert_eq!(complexity(tree.root_node(), source.as_bytes()), 5);
+
+        let source = "def f(x):\n    return 1 if x or not x else 2\n";
+        let tree = parse_source(Some("py"), source).unwrap();
+        assert_eq!(complexity(tree.root_node(), source.as_bytes()), 3);
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn hotspots_format() {
+        let output = hotspots_report(None, 365, 10).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
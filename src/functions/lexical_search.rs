@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::chunks::{chunks_of_source, Chunk};
+use super::common::{cache_file, content_hash, path_spills_up, walk_files};
+
+/// Files larger than this are most likely generated, and not worth indexing.
+const MAX_FILE_SIZE: u64 = 1 << 20;
+
+/// BM25 parameters: term frequency saturation, and how much chunk length matters.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Strip the most common English suffixes, so that `retries`, `retrying` and `retry` meet.
+fn stem(word: &str) -> String {
+    let mut word = word.to_string();
+    if word.len() > 4 && (word.ends_with("ies") || word.ends_with("ied")) {
+        word.truncate(word.len() - 3);
+        word.push('y');
+    } else if word.len() > 5 && word.ends_with("ing") {
+        word.truncate(word.len() - 3);
+    } else if word.len() > 4 && word.ends_with("ed") {
+        word.truncate(word.len() - 2);
+    } else if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+        word.truncate(word.len() - 1);
+    }
+    if word.len() > 3 && word.ends_with('e') {
+        word.truncate(word.len() - 1);
+    }
+    word
+}
+
+/// `parseHTTPResponse` -> `[parse, http, response]`
+fn split_identifier(identifier: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    for part in identifier.split('_') {
+        let chars: Vec<char> = part.chars().collect();
+        let mut start = 0;
+        for index in 1..chars.len() {
+            let (before, current) = (chars[index - 1], chars[index]);
+            let next = chars.get(index + 1);
+            let lower_to_upper = before.is_lowercase() && current.is_uppercase();
+            let acronym_end = before.is_uppercase()
+                && current.is_uppercase()
+                && next.is_some_and(|next| next.is_lowercase());
+            if lower_to_upper || acronym_end {
+                result.push(chars[start..index].iter().collect());
+                start = index;
+            }
+        }
+        result.push(chars[start..].iter().collect());
+    }
+    result
+}
+
+/// Split the text into search terms: identifiers, their words, lowercased and stemmed.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let identifiers = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|identifier| !identifier.is_empty());
+    for identifier in identifiers {
+        let words = split_identifier(identifier);
+        if words.len() > 1 {
+            result.push(identifier.to_lowercase());
+        }
+        for word in words {
+            if word.chars().count() > 1 {
+                result.push(stem(&word.to_lowercase()));
+            }
+        }
+    }
+    result
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedChunk {
+    chunk: Chunk,
+    length: usize,
+    terms: HashMap<String, u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    hash: u64,
+    chunks: Vec<IndexedChunk>,
+}
+
+/// Term counts for every chunk of every file, persisted between runs.
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    files: HashMap<PathBuf, IndexedFile>,
+}
+
+impl Index {
+    /// Load the index from the disk, and re-index whatever changed since.
+    fn load_and_refresh() -> io::Result<Self> {
+        let location = cache_file("lexical")?;
+        let mut index: Index = std::fs::read(&location)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        let mut changed = false;
+        let mut present = HashSet::new();
+        for file in walk_files(Path::new("."))? {
+            let Ok(metadata) = std::fs::metadata(&file) else {
+                continue;
+            };
+            if metadata.len() > MAX_FILE_SIZE {
+                continue;
+            }
+            let Ok(source_code) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            let hash = content_hash(source_code.as_bytes());
+            present.insert(file.clone());
+            if index
+                .files
+                .get(&file)
+                .is_some_and(|indexed| indexed.hash == hash)
+            {
+                continue;
+            }
+
+            let ext = file.extension().and_then(|ext| ext.to_str());
+            let chunks = chunks_of_source(ext, &source_code)
+                .into_iter()
+                .map(|chunk| {
+                    let tokens = tokenize(chunk.text(&source_code));
+                    let mut terms = HashMap::new();
+                    for token in &tokens {
+                        *terms.entry(token.clone()).or_default() += 1;
+                    }
+                    IndexedChunk {
+                        chunk,
+                        length: tokens.len(),
+                        terms,
+                    }
+                })
+                .collect();
+            index.files.insert(file, IndexedFile { hash, chunks });
+            changed = true;
+        }
+        let before = index.files.len();
+        index.files.retain(|file, _| present.contains(file));
+        changed |= index.files.len() != before;
+
+        if changed {
+            let bytes = serde_json::to_vec(&index).map_err(io::Error::other)?;
+            std::fs::write(&location, bytes)?;
+        }
+        Ok(index)
+    }
+
+    /// The best scoring chunks for the query, best first.
+    fn search(&self, query: &str, within: &Path, limit: usize) -> Vec<(f64, &Path, &Chunk)> {
+        let query: HashSet<String> = tokenize(query).into_iter().collect();
+        let all_chunks = || {
+            self.files
+                .iter()
+                .flat_map(|(file, indexed)| indexed.chunks.iter().map(move |chunk| (file, chunk)))
+        };
+
+        let count = all_chunks().count().max(1) as f64;
+        let average_length =
+            all_chunks().map(|(_, chunk)| chunk.length).sum::<usize>() as f64 / count;
+        let idf: HashMap<&str, f64> = query
+            .iter()
+            .map(|term| {
+                let frequency = all_chunks()
+                    .filter(|(_, chunk)| chunk.terms.contains_key(term))
+                    .count() as f64;
+                let idf = ((count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+                (term.as_str(), idf)
+            })
+            .collect();
+
+        let mut scored: Vec<(f64, &Path, &Chunk)> = all_chunks()
+            .filter(|(file, _)| file.starts_with(within))
+            .filter_map(|(file, indexed)| {
+                let length_ratio = indexed.length as f64 / average_length.max(1.0);
+                let score: f64 = idf
+                    .iter()
+                    .filter_map(|(term, idf)| {
+                        let frequency = f64::from(*indexed.terms.get(*term)?);
+                        let saturation = frequency * (K1 + 1.0)
+                            / (frequency + K1 * (1.0 - B + B * length_ratio));
+                        Some(idf * saturation)
+                    })
+                    .sum();
+                (score > 0.0).then_some((score, file.as_path(), &indexed.chunk))
+            })
+            .collect();
+        scored.sort_by(|one, another| another.0.total_cmp(&one.0));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+/// The lines of the chunk that mention the most of the query terms.
+fn snippet(source_code: &str, chunk: &Chunk, query: &str) -> String {
+    let query: HashSet<String> = tokenize(query).into_iter().collect();
+    let mut lines: Vec<(usize, usize, &str)> = chunk
+        .text(source_code)
+        .lines()
+        .enumerate()
+        .map(|(offset, line)| {
+            let hits = tokenize(line)
+                .iter()
+                .filter(|token| query.contains(*token))
+                .count();
+            (hits, chunk.start_line + offset, line)
+        })
+        .filter(|(hits, _, _)| *hits > 0)
+        .collect();
+    lines.sort_by_key(|(hits, line, _)| (std::cmp::Reverse(*hits), *line));
+    lines.truncate(3);
+    lines.sort_by_key(|(_, line, _)| *line);
+    lines
+        .iter()
+        .map(|(_, number, line)| format!("{number:>6} | {}\n", line.trim_end()))
+        .collect()
+}
+
+/// Rank the chunks of the codebase by how well they match the query.
+fn search_with_query(query: &str, within: &Path, limit: usize) -> io::Result<String> {
+    let within = within.strip_prefix(".").unwrap_or(within);
+    let index = Index::load_and_refresh()?;
+    let mut result = String::new();
+    for (score, file, chunk) in index.search(query, within, limit) {
+        let name = chunk
+            .name
+            .as_ref()
+            .map(|name| format!(" `{name}`"))
+            .unwrap_or_default();
+        result.push_str(&format!(
+            "{}:{}-{}{name} (score {score:.2})\n",
+            file.display(),
+            chunk.start_line,
+            chunk.end_line
+        ));
+        if let Ok(source_code) = std::fs::read_to_string(file) {
+            result.push_str(&snippet(&source_code, chunk, query));
+        }
+    }
+    if result.is_empty() {
+        result.push_str("nothing matches\n");
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `rg`, but ranked
+    pub fn lexical_search(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            query: String,
+            path: Option<String>,
+            limit: Option<usize>,
+        }
+        let Arguments { query, path, limit } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let path = PathBuf::from(path.unwrap_or_else(|| ".".into()));
+        if path.is_absolute() || path_spills_up(&path) {
+            return Err("cannot read files outside the current directory".into());
+        }
+        let limit = limit.unwrap_or(10).clamp(1, 50);
+
+        search_with_query(&query, &path, limit).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identifier_aware_tokens() {
+        assert_eq!(
+            tokenize("parseHTTPResponse(retry_count)"),
+            [
+                "parsehttpresponse",
+                "pars",
+                "http",
+                "respons",
+                "retry_count",
+                "retry",
+                "count"
+            ]
+        );
+        assert_eq!(tokenize("retries"), tokenize("retry"));
+        assert_eq!(tokenize("handled"), tokenize("handling"));
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn search_format() {
+        let output = search_with_query("where do we handle retries?", Path::new("."), 5).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
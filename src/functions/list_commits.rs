@@ -1,25 +1,160 @@
 /* spell-checker:words chrono revwalk */
 
-pub fn list_commits_in_current_repo() -> Result<String, String> {
+use git2::{Commit, DiffOptions, Repository, Sort};
+
+use super::common::pathspec_in_repo;
+
+/// How many commits to show when the model does not say.
+const DEFAULT_MAX_COUNT: usize = 50;
+
+/// What to pick from the history, like the arguments to `git log`.
+#[derive(Default)]
+pub struct LogFilter {
+    pub max_count: Option<usize>,
+    pub offset: usize,
+    pub path: Option<String>,
+    pub author: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub message: Option<String>,
+    pub rev: Option<String>,
+    pub first_parent: bool,
+}
+
+/// `2024-05-01` or `2024-05-01T12:00:00Z` as seconds since the epoch.
+fn parse_date(date: &str, end_of_day: bool) -> Result<i64, String> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Ok(datetime.timestamp());
+    }
+    let day = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("cannot parse the date `{date}`, expected YYYY-MM-DD"))?;
+    let time = if end_of_day {
+        day.and_hms_opt(23, 59, 59)
+    } else {
+        day.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.unwrap_or_default().and_utc().timestamp())
+}
+
+/// True if the commit changed anything under the pathspec compared to its first parent.
+fn touches(repo: &Repository, commit: &Commit, pathspec: &str) -> Result<bool, String> {
+    let tree = commit.tree().map_err(|err| err.to_string())?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().map_err(|err| err.to_string())?),
+        Err(_) => None,
+    };
+    let mut options = DiffOptions::new();
+    options.pathspec(pathspec);
+    let diff = repo
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+        .map_err(|err| err.to_string())?;
+    Ok(diff.deltas().len() > 0)
+}
+
+pub fn list_commits_in_current_repo(filter: &LogFilter) -> Result<String, String> {
     let repo = git2::Repository::discover(".").map_err(|err| err.to_string())?;
 
+    let pathspec = pathspec_in_repo(&repo, filter.path.as_deref())?;
+    let since = filter
+        .since
+        .as_deref()
+        .map(|date| parse_date(date, false))
+        .transpose()?;
+    let until = filter
+        .until
+        .as_deref()
+        .map(|date| parse_date(date, true))
+        .transpose()?;
+    let author = filter.author.as_deref().map(str::to_lowercase);
+    let message = filter.message.as_deref().map(str::to_lowercase);
+    let max_count = filter.max_count.unwrap_or(DEFAULT_MAX_COUNT);
+
     let mut result = String::new();
     let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
-    revwalk.push_head().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TIME)
+        .map_err(|err| err.to_string())?;
+    match filter.rev.as_deref() {
+        Some(rev) => {
+            let start = repo
+                .revparse_single(rev)
+                .and_then(|object| object.peel_to_commit())
+                .map_err(|err| format!("cannot resolve `{rev}`: {err}"))?;
+            revwalk.push(start.id())
+        }
+        None => revwalk.push_head(),
+    }
+    .map_err(|err| err.to_string())?;
+    if filter.first_parent {
+        revwalk
+            .simplify_first_parent()
+            .map_err(|err| err.to_string())?;
+    }
+
+    let mut matched = 0;
+    let mut more_remain = false;
     for rev in revwalk {
         let rev = rev.map_err(|err| err.to_string())?;
         let commit = repo.find_commit(rev).map_err(|err| err.to_string())?;
-        let date = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+        let seconds = commit.time().seconds();
+        if until.is_some_and(|until| seconds > until) {
+            continue;
+        }
+        // The walk goes from the newest, so nothing older can match either.
+        if since.is_some_and(|since| seconds < since) {
+            break;
+        }
+        let signature = commit.author();
+        let author_name = signature.name().unwrap_or_default();
+        if let Some(author) = &author {
+            let email = signature.email().unwrap_or_default();
+            if !author_name.to_lowercase().contains(author)
+                && !email.to_lowercase().contains(author)
+            {
+                continue;
+            }
+        }
+        if let Some(message) = &message {
+            let full = commit.message().unwrap_or_default().to_lowercase();
+            if !full.contains(message) {
+                continue;
+            }
+        }
+        if let Some(pathspec) = &pathspec {
+            if !touches(&repo, &commit, pathspec)? {
+                continue;
+            }
+        }
+
+        matched += 1;
+        if matched <= filter.offset {
+            continue;
+        }
+        if matched > filter.offset + max_count {
+            more_remain = true;
+            break;
+        }
+
+        let date = chrono::DateTime::from_timestamp(seconds, 0)
             .map(|dt| dt.to_rfc3339())
             .unwrap_or_default();
         let hash = commit.id().to_string();
         let summary = commit.summary().unwrap_or_default();
-        let author = commit.author();
-        let author = author.name().unwrap_or_default();
 
-        result.push_str(&format!("[{date}] [{hash}] | {summary} {{{author}}}\n"));
+        result.push_str(&format!(
+            "[{date}] [{hash}] | {summary} {{{author_name}}}\n"
+        ));
     }
 
+    if result.is_empty() {
+        result.push_str("no commits match\n");
+    }
+    if more_remain {
+        result.push_str(&format!(
+            "more commits remain, pass offset {} to see the next ones\n",
+            filter.offset + max_count
+        ));
+    }
     Ok(result)
 }
 
@@ -27,19 +162,73 @@ pub mod rpc {
     use super::*;
 
     /// `git log`
-    pub fn list_commits(_arguments: &str) -> Result<String, String> {
-        list_commits_in_current_repo()
+    pub fn list_commits(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            max_count: Option<usize>,
+            offset: Option<usize>,
+            path: Option<String>,
+            author: Option<String>,
+            since: Option<String>,
+            until: Option<String>,
+            message: Option<String>,
+            rev: Option<String>,
+            first_parent: Option<bool>,
+        }
+        // Older conversations call it without any arguments at all.
+        let arguments = if arguments.trim().is_empty() {
+            "{}"
+        } else {
+            arguments
+        };
+        let Arguments {
+            max_count,
+            offset,
+            path,
+            author,
+            since,
+            until,
+            message,
+            rev,
+            first_parent,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        list_commits_in_current_repo(&LogFilter {
+            max_count: max_count.map(|count| count.clamp(1, 1000)),
+            offset: offset.unwrap_or(0),
+            path,
+            author,
+            since,
+            until,
+            message,
+            rev,
+            first_parent: first_parent.unwrap_or(false),
+        })
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1970-01-02", false), Ok(86400));
+        assert_eq!(parse_date("1970-01-01", true), Ok(86399));
+        assert_eq!(parse_date("1970-01-01T00:01:00Z", false), Ok(60));
+        assert!(parse_date("yesterday", false).is_err());
+    }
+
     #[test]
     #[ignore = "run manually to see output"]
     fn commits_listing_format() {
-        let result = list_commits_in_current_repo().unwrap();
+        let filter = LogFilter {
+            max_count: Some(5),
+            path: Some("src/functions".into()),
+            ..Default::default()
+        };
+        let result = list_commits_in_current_repo(&filter).unwrap();
         println!("{}", result);
         assert!(false);
     }
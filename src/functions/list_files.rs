@@ -5,6 +5,9 @@ use std::{
     path::Path,
 };
 
+use git2::ObjectType;
+
+use super::at_revision::{entry_at_revision, Entry};
 use super::common::path_spills_up;
 
 /// `0b111` -> `rwx`.
@@ -83,6 +86,38 @@ fn list_files_with_path(path: &Path) -> std::io::Result<String> {
     Ok(result)
 }
 
+/// `git ls-tree -l rev path`, in the shape of [list_files_with_path].
+fn list_files_at_revision(path: &Path, rev: &str) -> io::Result<String> {
+    if path.is_absolute() || path_spills_up(path) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot list files outside the current directory",
+        ));
+    }
+    let Entry::Directory(entries) = entry_at_revision(rev, path)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is a file at `{rev}`", path.display()),
+        ));
+    };
+    let mut result = String::new();
+    for entry in entries {
+        let (kind, perms) = match entry.kind {
+            Some(ObjectType::Tree) => ("d", 0o755),
+            Some(ObjectType::Commit) => ("m", 0o755),
+            _ if entry.mode == 0o120000 => ("l", 0o777),
+            _ => ("-", entry.mode as u32 & 0o777),
+        };
+        result.push_str(&format!(
+            "{kind}{mode} {size:>8}B {name}\n",
+            mode = rwx(perms),
+            size = entry.size.unwrap_or(0),
+            name = entry.name,
+        ));
+    }
+    Ok(result)
+}
+
 pub mod rpc {
     use super::*;
 
@@ -91,14 +126,21 @@ pub mod rpc {
         #[derive(serde::Deserialize)]
         struct Arguments {
             path: String,
+            rev: Option<String>,
         }
-        let Arguments { path } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+        let Arguments { path, rev } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
 
-        list_files_with_path(Path::new(&path)).map_err(|err| err.to_string())
+        match rev {
+            Some(rev) => list_files_at_revision(Path::new(&path), &rev),
+            None => list_files_with_path(Path::new(&path)),
+        }
+        .map_err(|err| err.to_string())
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod test {
     use super::*;
 
@@ -109,4 +151,12 @@ mod test {
         eprintln!("{}", output);
         assert!(false);
     }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn directory_listing_at_revision_format() {
+        let output = list_files_at_revision(Path::new("src"), "HEAD~1").unwrap();
+        eprintln!("{}", output);
+        assert!(false);
+    }
 }
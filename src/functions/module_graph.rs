@@ -0,0 +1,952 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::json;
+use tree_sitter::Node;
+
+use super::common::{path_spills_up, walk_files};
+use super::query_ast::parse_source;
+
+/// How one module came to depend on another.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EdgeKind {
+    /// `mod child;`
+    Declares,
+    /// `use`, `import`, `from ... import`, `import ... from`.
+    Imports,
+}
+
+impl EdgeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            EdgeKind::Declares => "declares",
+            EdgeKind::Imports => "imports",
+        }
+    }
+}
+
+/// One module depending on another.
+#[derive(Clone, Debug)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+    /// One-based line of the statement in `from`.
+    pub line: usize,
+}
+
+/// How to print the graph.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GraphFormat {
+    Text,
+    Dot,
+    Json,
+    Mermaid,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(GraphFormat::Text),
+            "dot" => Ok(GraphFormat::Dot),
+            "json" => Ok(GraphFormat::Json),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!(
+                "unknown graph format `{s}`, expected one of `text`, `dot`, `json`, `mermaid`"
+            )),
+        }
+    }
+}
+
+/// Files of the codebase, and which of them refer to which.
+pub struct ModuleGraph {
+    pub nodes: Vec<PathBuf>,
+    pub edges: Vec<Edge>,
+}
+
+impl ModuleGraph {
+    /// Parse every supported file under the given directory
+    /// and resolve its imports to the other files in there.
+    pub fn of_directory(path: &Path) -> io::Result<Self> {
+        let nodes: Vec<PathBuf> = walk_files(path)?
+            .into_iter()
+            .filter(|file| parse_source(extension_of(file), "").is_some())
+            .collect();
+        let index: HashMap<&Path, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.as_path(), index))
+            .collect();
+
+        let mut edges = Vec::new();
+        for (from, file) in nodes.iter().enumerate() {
+            let Ok(source_code) = std::fs::read_to_string(file) else {
+                continue;
+            };
+            let resolved = match extension_of(file) {
+                Some("rs") => rust::dependencies(file, &source_code, &index),
+                Some("py") => python::dependencies(file, &source_code, &index),
+                _ => typescript::dependencies(file, &source_code, &index),
+            };
+            for (to, kind, line) in resolved {
+                let duplicate = edges
+                    .iter()
+                    .any(|edge: &Edge| edge.from == from && edge.to == to && edge.kind == kind);
+                if to != from && !duplicate {
+                    edges.push(Edge {
+                        from,
+                        to,
+                        kind,
+                        line,
+                    });
+                }
+            }
+        }
+        Ok(Self { nodes, edges })
+    }
+
+    /// Groups of modules importing each other in a loop, each as a closed walk.
+    ///
+    /// Only imports count: a parent declaring a child that imports from the parent
+    /// is how Rust modules usually look, and not a layering problem.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut successors = vec![Vec::new(); self.nodes.len()];
+        for edge in &self.edges {
+            if edge.kind == EdgeKind::Imports {
+                successors[edge.from].push(edge.to);
+            }
+        }
+
+        let mut result = Vec::new();
+        for component in strongly_connected_components(&successors) {
+            if component.len() < 2 {
+                continue;
+            }
+            let members: HashSet<usize> = component.iter().copied().collect();
+            let start = *component.iter().min().unwrap();
+            let within = |node: usize| {
+                successors[node]
+                    .iter()
+                    .copied()
+                    .filter(|n| members.contains(n))
+            };
+            let Some(mut walk) = shortest_path(start, |node| node == start, within) else {
+                continue;
+            };
+            walk.insert(0, start);
+            result.push(walk);
+        }
+        result
+    }
+
+    /// The shortest chain of dependencies leading from one group of modules to another.
+    pub fn dependency_path(&self, from: &str, to: &str) -> Option<Vec<usize>> {
+        let mut successors = vec![Vec::new(); self.nodes.len()];
+        for edge in &self.edges {
+            successors[edge.from].push(edge.to);
+        }
+        (0..self.nodes.len())
+            .filter(|&node| node_matches(&self.nodes[node], from))
+            .filter_map(|start| {
+                let mut path = shortest_path(
+                    start,
+                    |node| node_matches(&self.nodes[node], to),
+                    |node| successors[node].iter().copied(),
+                )?;
+                path.insert(0, start);
+                Some(path)
+            })
+            .min_by_key(Vec::len)
+    }
+
+    /// Only the edges leaving and entering the matching modules.
+    pub fn filtered(&self, from: Option<&str>, to: Option<&str>) -> Vec<&Edge> {
+        self.edges
+            .iter()
+            .filter(|edge| from.is_none_or(|from| node_matches(&self.nodes[edge.from], from)))
+            .filter(|edge| to.is_none_or(|to| node_matches(&self.nodes[edge.to], to)))
+            .collect()
+    }
+
+    /// `a -> b -> c`
+    pub fn describe_walk(&self, walk: &[usize]) -> String {
+        walk.iter()
+            .map(|&node| self.nodes[node].display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+
+    /// Print the given edges of the graph in the given format.
+    /// Every format reports the cycles: DOT and Mermaid as comments, with their edges in red.
+    pub fn render(&self, edges: &[&Edge], format: GraphFormat) -> String {
+        let name = |node: usize| self.nodes[node].display().to_string();
+        let cycles = self.cycles();
+        let in_cycle = |edge: &Edge| {
+            edge.kind == EdgeKind::Imports
+                && cycles
+                    .iter()
+                    .any(|cycle| cycle.windows(2).any(|step| step == [edge.from, edge.to]))
+        };
+        let mut result = String::new();
+        match format {
+            GraphFormat::Text => {
+                for edge in edges {
+                    result.push_str(&format!(
+                        "{} -> {} ({}, line {})\n",
+                        name(edge.from),
+                        name(edge.to),
+                        edge.kind.as_str(),
+                        edge.line
+                    ));
+                }
+                if !cycles.is_empty() {
+                    result.push_str("\ncycles:\n");
+                    for cycle in &cycles {
+                        result.push_str(&format!("{}\n", self.describe_walk(cycle)));
+                    }
+                }
+            }
+            GraphFormat::Dot => {
+                result.push_str("digraph modules {\n");
+                for cycle in &cycles {
+                    result.push_str(&format!("    // cycle: {}\n", self.describe_walk(cycle)));
+                }
+                for node in 0..self.nodes.len() {
+                    result.push_str(&format!("    {:?};\n", name(node)));
+                }
+                for edge in edges {
+                    let style = match edge.kind {
+                        EdgeKind::Declares => " [style=dashed]",
+                        EdgeKind::Imports if in_cycle(edge) => " [color=red]",
+                        EdgeKind::Imports => "",
+                    };
+                    result.push_str(&format!(
+                        "    {:?} -> {:?}{style};\n",
+                        name(edge.from),
+                        name(edge.to)
+                    ));
+                }
+                result.push_str("}\n");
+            }
+            GraphFormat::Mermaid => {
+                result.push_str("graph LR\n");
+                for cycle in &cycles {
+                    result.push_str(&format!("    %% cycle: {}\n", self.describe_walk(cycle)));
+                }
+                for node in 0..self.nodes.len() {
+                    result.push_str(&format!("    n{node}[\"{}\"]\n", name(node)));
+                }
+                for edge in edges {
+                    let arrow = match edge.kind {
+                        EdgeKind::Declares => "-.->",
+                        EdgeKind::Imports => "-->",
+                    };
+                    result.push_str(&format!("    n{} {arrow} n{}\n", edge.from, edge.to));
+                }
+                let links: Vec<String> = (0..edges.len())
+                    .filter(|&link| in_cycle(edges[link]))
+                    .map(|link| link.to_string())
+                    .collect();
+                if !links.is_empty() {
+                    result.push_str(&format!("    linkStyle {} stroke:red\n", links.join(",")));
+                }
+            }
+            GraphFormat::Json => {
+                let nodes: Vec<_> = (0..self.nodes.len()).map(name).collect();
+                let edges: Vec<_> = edges
+                    .iter()
+                    .map(|edge| {
+                        json!({
+                            "from": name(edge.from),
+                            "to": name(edge.to),
+                            "kind": edge.kind.as_str(),
+                            "line": edge.line,
+                        })
+                    })
+                    .collect();
+                let cycles: Vec<Vec<String>> = cycles
+                    .iter()
+                    .map(|cycle| cycle.iter().map(|&node| name(node)).collect())
+                    .collect();
+                let graph = json!({ "nodes": nodes, "edges": edges, "cycles": cycles });
+                result.push_str(&serde_json::to_string_pretty(&graph).unwrap());
+                result.push('\n');
+            }
+        }
+        result
+    }
+}
+
+fn extension_of(path: &Path) -> Option<&str> {
+    path.extension().and_then(|ext| ext.to_str())
+}
+
+/// True if the pattern names the module or the directory it is in,
+/// by its path like `src/io`, or by its module path like `io` or `crate::openai::schema`.
+fn node_matches(node: &Path, pattern: &str) -> bool {
+    let path = Path::new(pattern.trim_start_matches("./"));
+    if node == path || node.with_extension("") == path || node.starts_with(path) {
+        return true;
+    }
+    let module: Vec<&str> = pattern.trim_start_matches("crate::").split("::").collect();
+    let stem = node.with_extension("");
+    let mut segments: Vec<&str> = stem
+        .components()
+        .filter_map(|component| component.as_os_str().to_str())
+        .collect();
+    if segments.last() == Some(&"mod") {
+        segments.pop();
+    }
+    !module.contains(&"")
+        && segments
+            .windows(module.len())
+            .any(|window| window == module)
+}
+
+/// Resolve `.` and `..` without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push("..");
+                }
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+/// Call the visitor for every node of the tree, parents before children.
+fn visit<'tree>(node: Node<'tree>, visitor: &mut impl FnMut(Node<'tree>)) {
+    visitor(node);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        visit(child, visitor);
+    }
+}
+
+/// Breadth-first search for the nearest node satisfying the goal,
+/// not counting the start itself. The start is not included in the path.
+fn shortest_path<Successors, Iter>(
+    start: usize,
+    goal: impl Fn(usize) -> bool,
+    successors: Successors,
+) -> Option<Vec<usize>>
+where
+    Successors: Fn(usize) -> Iter,
+    Iter: Iterator<Item = usize>,
+{
+    let mut previous = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for next in successors(node) {
+            if previous.contains_key(&next) {
+                continue;
+            }
+            previous.insert(next, node);
+            if goal(next) {
+                let mut path = vec![next];
+                let mut current = next;
+                while let Some(&before) = previous.get(&current) {
+                    if before == start {
+                        break;
+                    }
+                    path.push(before);
+                    current = before;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+/// Tarjan's algorithm over an adjacency list.
+fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        successors: &'a [Vec<usize>],
+        counter: usize,
+        index: Vec<Option<usize>>,
+        lowlink: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        result: Vec<Vec<usize>>,
+    }
+
+    fn connect(state: &mut State, node: usize) {
+        state.index[node] = Some(state.counter);
+        state.lowlink[node] = state.counter;
+        state.counter += 1;
+        state.stack.push(node);
+        state.on_stack[node] = true;
+
+        for &next in &state.successors[node] {
+            match state.index[next] {
+                None => {
+                    connect(state, next);
+                    state.lowlink[node] = state.lowlink[node].min(state.lowlink[next]);
+                }
+                Some(index) if state.on_stack[next] => {
+                    state.lowlink[node] = state.lowlink[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(state.lowlink[node]) == state.index[node] {
+            let mut component = Vec::new();
+            while let Some(member) = state.stack.pop() {
+                state.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            state.result.push(component);
+        }
+    }
+
+    let count = successors.len();
+    let mut state = State {
+        successors,
+        counter: 0,
+        index: vec![None; count],
+        lowlink: vec![0; count],
+        on_stack: vec![false; count],
+        stack: Vec::new(),
+        result: Vec::new(),
+    };
+    for node in 0..count {
+        if state.index[node].is_none() {
+            connect(&mut state, node);
+        }
+    }
+    state.result
+}
+
+mod rust {
+    use super::*;
+
+    /// Where `crate::` points to for the given file:
+    /// the nearest directory up the tree with a `main.rs` or a `lib.rs`.
+    fn crate_root(file: &Path, index: &HashMap<&Path, usize>) -> Option<PathBuf> {
+        file.ancestors().skip(1).find_map(|directory| {
+            ["main.rs", "lib.rs"]
+                .iter()
+                .any(|root| index.contains_key(directory.join(root).as_path()))
+                .then(|| directory.to_path_buf())
+        })
+    }
+
+    /// `src/openai/schema.rs` -> `["openai", "schema"]`
+    fn module_path_of_file(root: &Path, file: &Path) -> Vec<String> {
+        let Ok(relative) = file.strip_prefix(root) else {
+            return Vec::new();
+        };
+        let mut segments: Vec<String> = relative
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        if matches!(
+            segments.last().map(String::as_str),
+            Some("mod" | "main" | "lib")
+        ) {
+            segments.pop();
+        }
+        segments
+    }
+
+    /// `["openai", "schema"]` -> `src/openai/schema.rs`, if there is such a file.
+    fn file_of_module_path(
+        root: &Path,
+        segments: &[String],
+        index: &HashMap<&Path, usize>,
+    ) -> Option<usize> {
+        let candidates = if segments.is_empty() {
+            vec![root.join("main.rs"), root.join("lib.rs")]
+        } else {
+            let joined: PathBuf = segments.iter().collect();
+            vec![
+                root.join(&joined).with_extension("rs"),
+                root.join(&joined).join("mod.rs"),
+            ]
+        };
+        candidates
+            .iter()
+            .find_map(|candidate| index.get(candidate.as_path()).copied())
+    }
+
+    /// `a::{b, c::{d as e, *}}` -> `[[a, b], [a, c, d], [a, c]]`
+    pub(super) fn expand_use_tree(text: &str) -> Vec<Vec<String>> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut result = Vec::new();
+        expand_into(&text, &[], &mut result);
+        result
+    }
+
+    fn expand_into(text: &str, prefix: &[String], result: &mut Vec<Vec<String>>) {
+        let text = text.trim();
+        if let Some(open) = text.find('{') {
+            let head: Vec<String> = text[..open]
+                .split("::")
+                .map(str::trim)
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_string)
+                .collect();
+            let prefix = [prefix, &head].concat();
+            let inner = &text[open + 1..text.rfind('}').unwrap_or(text.len())];
+            let mut depth = 0;
+            let mut start = 0;
+            for (position, c) in inner.char_indices() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    ',' if depth == 0 => {
+                        expand_into(&inner[start..position], &prefix, result);
+                        start = position + 1;
+                    }
+                    _ => {}
+                }
+            }
+            expand_into(&inner[start..], &prefix, result);
+            return;
+        }
+        let text = text.split(" as ").next().unwrap_or(text);
+        let mut path = prefix.to_vec();
+        for segment in text.split("::").map(str::trim) {
+            if !segment.is_empty() && segment != "*" && segment != "self" {
+                path.push(segment.to_string());
+            }
+        }
+        if !path.is_empty() {
+            result.push(path);
+        }
+    }
+
+    /// Follow the path from the current module as deep as there are files for it.
+    fn resolve(
+        root: &Path,
+        current: &[String],
+        path: &[String],
+        index: &HashMap<&Path, usize>,
+    ) -> Option<usize> {
+        let mut base = current.to_vec();
+        let mut rest = path;
+        match rest.first().map(String::as_str) {
+            Some("crate") => {
+                base.clear();
+                rest = &rest[1..];
+            }
+            Some("self") => rest = &rest[1..],
+            Some("super") => {
+                while rest.first().map(String::as_str) == Some("super") {
+                    base.pop();
+                    rest = &rest[1..];
+                }
+            }
+            // Either a child module, or another crate.
+            Some(first) => {
+                let child = [current, &[first.to_string()]].concat();
+                file_of_module_path(root, &child, index)?;
+            }
+            None => return None,
+        }
+        let mut found = file_of_module_path(root, &base, index);
+        for segment in rest {
+            base.push(segment.clone());
+            match file_of_module_path(root, &base, index) {
+                Some(deeper) => found = Some(deeper),
+                None => break,
+            }
+        }
+        found
+    }
+
+    /// Names of the inline `mod name { ... }` blocks the node is nested in, outermost first.
+    fn inline_modules(node: Node, source_code: &str) -> Vec<String> {
+        let mut result = Vec::new();
+        let mut current = node.parent();
+        while let Some(parent) = current {
+            if parent.kind() == "mod_item" {
+                if let Some(name) = parent.child_by_field_name("name") {
+                    let name = name.utf8_text(source_code.as_bytes()).unwrap_or_default();
+                    result.push(name.to_string());
+                }
+            }
+            current = parent.parent();
+        }
+        result.reverse();
+        result
+    }
+
+    pub fn dependencies(
+        file: &Path,
+        source_code: &str,
+        index: &HashMap<&Path, usize>,
+    ) -> Vec<(usize, EdgeKind, usize)> {
+        let Some(tree) = parse_source(Some("rs"), source_code) else {
+            return Vec::new();
+        };
+        let Some(root) = crate_root(file, index) else {
+            return Vec::new();
+        };
+        let module = module_path_of_file(&root, file);
+
+        let mut result = Vec::new();
+        visit(tree.root_node(), &mut |node| {
+            let line = node.start_position().row + 1;
+            let current = [module.clone(), inline_modules(node, source_code)].concat();
+            let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap_or_default();
+            match node.kind() {
+                "mod_item" if node.child_by_field_name("body").is_none() => {
+                    let Some(name) = node.child_by_field_name("name") else {
+                        return;
+                    };
+                    let child = [current, vec![text(name).to_string()]].concat();
+                    if let Some(to) = file_of_module_path(&root, &child, index) {
+                        result.push((to, EdgeKind::Declares, line));
+                    }
+                }
+                "use_declaration" => {
+                    let Some(argument) = node.child_by_field_name("argument") else {
+                        return;
+                    };
+                    for path in expand_use_tree(text(argument)) {
+                        if let Some(to) = resolve(&root, &current, &path, index) {
+                            result.push((to, EdgeKind::Imports, line));
+                        }
+                    }
+                }
+                // Fully qualified paths in code, like `crate::io::show_reply(...)`.
+                "scoped_identifier" | "scoped_type_identifier" => {
+                    let nested = node.parent().is_some_and(|parent| {
+                        parent.kind().starts_with("scoped_") || parent.kind() == "use_declaration"
+                    });
+                    let path = text(node);
+                    if nested || !(path.starts_with("crate::") || path.starts_with("super::")) {
+                        return;
+                    }
+                    let path: Vec<String> = path
+                        .split('<')
+                        .next()
+                        .unwrap_or_default()
+                        .split("::")
+                        .map(str::to_string)
+                        .collect();
+                    if let Some(to) = resolve(&root, &current, &path, index) {
+                        result.push((to, EdgeKind::Imports, line));
+                    }
+                }
+                _ => {}
+            }
+        });
+        result
+    }
+}
+
+mod python {
+    use super::*;
+
+    /// `a.b.c` relative to the directory -> `a/b/c.py` or `a/b/c/__init__.py`.
+    fn module_file(
+        directory: &Path,
+        dotted: &[&str],
+        index: &HashMap<&Path, usize>,
+    ) -> Option<usize> {
+        let joined: PathBuf = directory
+            .iter()
+            .chain(dotted.iter().map(|s| s.as_ref()))
+            .collect();
+        let candidates = [joined.with_extension("py"), joined.join("__init__.py")];
+        candidates
+            .iter()
+            .find_map(|candidate| index.get(normalize(candidate).as_path()).copied())
+    }
+
+    /// The deepest module named by a prefix of the dotted path,
+    /// looking from the directory of the file and from each directory above it.
+    fn resolve_absolute(
+        file: &Path,
+        dotted: &[&str],
+        index: &HashMap<&Path, usize>,
+    ) -> Option<usize> {
+        file.ancestors().skip(1).find_map(|directory| {
+            (1..=dotted.len())
+                .rev()
+                .find_map(|length| module_file(directory, &dotted[..length], index))
+        })
+    }
+
+    pub fn dependencies(
+        file: &Path,
+        source_code: &str,
+        index: &HashMap<&Path, usize>,
+    ) -> Vec<(usize, EdgeKind, usize)> {
+        let Some(tree) = parse_source(Some("py"), source_code) else {
+            return Vec::new();
+        };
+
+        let mut result = Vec::new();
+        visit(tree.root_node(), &mut |node| {
+            let line = node.start_position().row + 1;
+            let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap_or_default();
+            let dotted_name = |node: Node| {
+                let node = match node.kind() {
+                    "aliased_import" => node.child_by_field_name("name"),
+                    _ => Some(node),
+                };
+                node.map(|node| {
+                    text(node)
+                        .split('.')
+                        .map(str::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+            };
+            match node.kind() {
+                "import_statement" => {
+                    let mut cursor = node.walk();
+                    for name in node.children_by_field_name("name", &mut cursor) {
+                        let dotted = dotted_name(name);
+                        let dotted: Vec<&str> = dotted.iter().map(String::as_str).collect();
+                        if let Some(to) = resolve_absolute(file, &dotted, index) {
+                            result.push((to, EdgeKind::Imports, line));
+                        }
+                    }
+                }
+                "import_from_statement" => {
+                    let Some(module_name) = node.child_by_field_name("module_name") else {
+                        return;
+                    };
+                    let module = text(module_name);
+                    let dots = module.chars().take_while(|&c| c == '.').count();
+                    let module: Vec<&str> = module[dots..]
+                        .split('.')
+                        .filter(|segment| !segment.is_empty())
+                        .collect();
+                    let mut cursor = node.walk();
+                    let names: Vec<Vec<String>> = node
+                        .children_by_field_name("name", &mut cursor)
+                        .map(dotted_name)
+                        .collect();
+                    // Each of the imported names may be a submodule, or just a name in the module.
+                    let mut targets: Vec<Vec<&str>> = names
+                        .iter()
+                        .map(|name| {
+                            [module.clone(), name.iter().map(String::as_str).collect()].concat()
+                        })
+                        .collect();
+                    targets.push(module.clone());
+                    for target in targets {
+                        let resolved = if dots == 0 {
+                            resolve_absolute(file, &target, index)
+                        } else {
+                            let mut directory =
+                                file.parent().unwrap_or(Path::new("")).to_path_buf();
+                            for _ in 1..dots {
+                                directory.push("..");
+                            }
+                            if target.is_empty() {
+                                module_file(&directory, &[], index)
+                            } else {
+                                module_file(&directory, &target, index)
+                            }
+                        };
+                        if let Some(to) = resolved {
+                            result.push((to, EdgeKind::Imports, line));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        });
+        result
+    }
+}
+
+mod typescript {
+    use super::*;
+
+    /// `./x` -> `./x.ts`, `./x/index.ts`, and the like.
+    fn module_file(file: &Path, specifier: &str, index: &HashMap<&Path, usize>) -> Option<usize> {
+        let base = normalize(&file.parent().unwrap_or(Path::new("")).join(specifier));
+        let stem = base.with_extension("");
+        let mut candidates = vec![base.clone()];
+        for ext in ["ts", "tsx", "js", "jsx"] {
+            candidates.push(PathBuf::from(format!("{}.{ext}", base.display())));
+            candidates.push(stem.with_extension(ext));
+            candidates.push(base.join("index").with_extension(ext));
+        }
+        candidates
+            .iter()
+            .find_map(|candidate| index.get(candidate.as_path()).copied())
+    }
+
+    pub fn dependencies(
+        file: &Path,
+        source_code: &str,
+        index: &HashMap<&Path, usize>,
+    ) -> Vec<(usize, EdgeKind, usize)> {
+        let Some(tree) = parse_source(extension_of(file), source_code) else {
+            return Vec::new();
+        };
+
+        let mut result = Vec::new();
+        visit(tree.root_node(), &mut |node| {
+            if !matches!(node.kind(), "import_statement" | "export_statement") {
+                return;
+            }
+            let Some(source) = node.child_by_field_name("source") else {
+                return;
+            };
+            let specifier = source
+                .utf8_text(source_code.as_bytes())
+                .unwrap_or_default()
+                .trim_matches(|c| c == '"' || c == '\'' || c == '`');
+            // Bare specifiers are packages, not files of ours.
+            if !specifier.starts_with('.') {
+                return;
+            }
+            if let Some(to) = module_file(file, specifier, index) {
+                result.push((to, EdgeKind::Imports, node.start_position().row + 1));
+            }
+        });
+        result
+    }
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `cargo modules dependencies`
+    pub fn module_graph(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: Option<String>,
+            from: Option<String>,
+            to: Option<String>,
+            format: Option<String>,
+        }
+        let Arguments {
+            path,
+            from,
+            to,
+            format,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let path = PathBuf::from(path.unwrap_or_else(|| ".".into()));
+        if path.is_absolute() || path_spills_up(&path) {
+            return Err("cannot read files outside the current directory".into());
+        }
+        let format = match format {
+            Some(format) => format.parse()?,
+            None => GraphFormat::Text,
+        };
+
+        let graph = ModuleGraph::of_directory(&path).map_err(|err| err.to_string())?;
+        if let (Some(from), Some(to)) = (&from, &to) {
+            return Ok(match graph.dependency_path(from, to) {
+                Some(walk) => format!("yes: {}\n", graph.describe_walk(&walk)),
+                None => format!("no: nothing in `{from}` depends on `{to}`\n"),
+            });
+        }
+        let edges = graph.filtered(from.as_deref(), to.as_deref());
+        Ok(graph.render(&edges, format))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn use_trees() {
+        let expand = |text| {
+            rust::expand_use_tree(text)
+                .into_iter()
+                .map(|path| path.join("::"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(expand("crate::openai::Chat"), ["crate::openai::Chat"]);
+        assert_eq!(
+            expand("super::{common::walk_files, query_ast::{self, parse_source as parse}}"),
+            [
+                "super::common::walk_files",
+                "super::query_ast",
+                "super::query_ast::parse_source"
+            ]
+        );
+        assert_eq!(expand("std::io::*"), ["std::io"]);
+    }
+
+    #[test]
+    fn cycles_and_paths() {
+        let node = |name: &str| PathBuf::from(name);
+        let edge = |from, to, kind| Edge {
+            from,
+            to,
+            kind,
+            line: 1,
+        };
+        let graph = ModuleGraph {
+            nodes: vec![node("a.rs"), node("b.rs"), node("c.rs"), node("d.rs")],
+            edges: vec![
+                edge(0, 1, EdgeKind::Imports),
+                edge(1, 2, EdgeKind::Imports),
+                edge(2, 0, EdgeKind::Imports),
+                edge(2, 3, EdgeKind::Declares),
+                edge(3, 2, EdgeKind::Imports),
+            ],
+        };
+        let cycles: Vec<_> = graph
+            .cycles()
+            .iter()
+            .map(|c| graph.describe_walk(c))
+            .collect();
+        assert_eq!(cycles, ["a.rs -> b.rs -> c.rs -> a.rs"]);
+        let path = graph.dependency_path("a", "d").unwrap();
+        assert_eq!(graph.describe_walk(&path), "a.rs -> b.rs -> c.rs -> d.rs");
+        assert!(graph.dependency_path("d", "nowhere").is_none());
+
+        let dot = graph.render(&graph.filtered(None, None), GraphFormat::Dot);
+        assert!(dot.contains("// cycle: a.rs -> b.rs -> c.rs -> a.rs"));
+        assert!(dot.contains("\"c.rs\" -> \"a.rs\" [color=red];"));
+        assert!(dot.contains("\"d.rs\" -> \"c.rs\";"));
+        let mermaid = graph.render(&graph.filtered(None, None), GraphFormat::Mermaid);
+        assert!(mermaid.contains("linkStyle 0,1,2 stroke:red"));
+    }
+
+    #[test]
+    fn module_names() {
+        let matches = |node: &str, pattern| node_matches(Path::new(node), pattern);
+        assert!(matches("src/io.rs", "src/io"));
+        assert!(matches("src/io.rs", "io"));
+        assert!(matches("src/io.rs", "crate::io"));
+        assert!(matches("src/io/throbber.rs", "io"));
+        assert!(matches("src/openai/schema.rs", "openai::schema"));
+        assert!(matches("src/functions/mod.rs", "crate::functions"));
+        assert!(!matches("src/ion.rs", "io"));
+        assert!(!matches("src/openai/schema.rs", "schema::openai"));
+        assert!(!matches("src/io.rs", "crate::"));
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn module_graph_format() {
+        let graph = ModuleGraph::of_directory(Path::new(".")).unwrap();
+        let edges = graph.filtered(None, None);
+        println!("{}", graph.render(&edges, GraphFormat::Text));
+        assert!(false);
+    }
+}
@@ -0,0 +1,225 @@
+/* spell-checker:words chrono mailmap revwalk */
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use git2::{BlameOptions, DiffOptions, Mailmap, Repository, Signature, Sort};
+
+use super::common::{path_in_repo, path_spills_up, walk_files};
+
+/// Blaming is the slow part, so stop after this many files.
+const MAX_FILES_BLAMED: usize = 300;
+
+/// How many people to list in each ranking.
+const MAX_PEOPLE: usize = 10;
+
+/// Tallies per person, merging the identities that share an address.
+#[derive(Default)]
+struct People {
+    counts: HashMap<String, usize>,
+    names: HashMap<String, HashMap<String, usize>>,
+}
+
+impl People {
+    fn add(&mut self, mailmap: Option<&Mailmap>, signature: &Signature, count: usize) {
+        let resolved = mailmap.and_then(|mailmap| mailmap.resolve_signature(signature).ok());
+        let signature = resolved.as_ref().unwrap_or(signature);
+        let name = signature.name().unwrap_or_default().to_string();
+        let email = signature.email().unwrap_or_default().to_lowercase();
+        let key = if email.is_empty() {
+            name.clone()
+        } else {
+            email
+        };
+        *self.counts.entry(key.clone()).or_default() += count;
+        *self.names.entry(key).or_default().entry(name).or_default() += count;
+    }
+
+    /// `name <email>`, going by the name used the most with that address.
+    fn describe(&self, key: &str) -> String {
+        let name = self
+            .names
+            .get(key)
+            .and_then(|names| names.iter().max_by_key(|(name, count)| (*count, *name)))
+            .map(|(name, _)| name.as_str())
+            .unwrap_or_default();
+        if name == key {
+            name.to_string()
+        } else {
+            format!("{name} <{key}>")
+        }
+    }
+
+    /// Everyone with their count, the largest first.
+    fn ranked(&self) -> Vec<(String, usize)> {
+        let mut ranked: Vec<(String, usize)> = self
+            .counts
+            .iter()
+            .map(|(key, count)| (self.describe(key), *count))
+            .collect();
+        ranked.sort_by(|one, another| another.1.cmp(&one.1).then_with(|| one.0.cmp(&another.0)));
+        ranked
+    }
+}
+
+/// How many people own more than half of the lines between them.
+fn bus_factor(owned: &[(String, usize)]) -> usize {
+    let total: usize = owned.iter().map(|(_, lines)| lines).sum();
+    let mut covered = 0;
+    for (count, (_, lines)) in owned.iter().enumerate() {
+        covered += lines;
+        if covered * 2 > total {
+            return count + 1;
+        }
+    }
+    owned.len()
+}
+
+/// Who knows the code under the path: by the lines they own now, and by their recent commits.
+pub fn ownership_of_path(path: &Path, days: u32) -> Result<String, String> {
+    if path.is_absolute() || path_spills_up(path) {
+        return Err("cannot read files outside the current directory".into());
+    }
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let mailmap = repo.mailmap().ok();
+    let relative = path_in_repo(&repo, path).ok_or("the path is not in the repository")?;
+
+    let files = if path.is_dir() {
+        walk_files(path).map_err(|err| err.to_string())?
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut owned = People::default();
+    let mut blamed = 0;
+    for file in files.iter().take(MAX_FILES_BLAMED) {
+        let Some(file) = path_in_repo(&repo, file) else {
+            continue;
+        };
+        let mut options = BlameOptions::new();
+        options.use_mailmap(true);
+        // Untracked and binary files have nobody to blame.
+        let Ok(blame) = repo.blame_file(&file, Some(&mut options)) else {
+            continue;
+        };
+        blamed += 1;
+        for hunk in blame.iter() {
+            owned.add(
+                mailmap.as_ref(),
+                &hunk.final_signature(),
+                hunk.lines_in_hunk(),
+            );
+        }
+    }
+
+    let since = chrono::Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+    let mut commits = People::default();
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TIME)
+        .map_err(|err| err.to_string())?;
+    revwalk.push_head().map_err(|err| err.to_string())?;
+    for id in revwalk {
+        let id = id.map_err(|err| err.to_string())?;
+        let commit = repo.find_commit(id).map_err(|err| err.to_string())?;
+        if commit.time().seconds() < since {
+            break;
+        }
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let tree = commit.tree().map_err(|err| err.to_string())?;
+        let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+        let mut options = DiffOptions::new();
+        if !relative.as_os_str().is_empty() {
+            options.pathspec(&relative);
+        }
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+            .map_err(|err| err.to_string())?;
+        if diff.deltas().len() == 0 {
+            continue;
+        }
+        commits.add(mailmap.as_ref(), &commit.author(), 1);
+    }
+
+    let owned = owned.ranked();
+    let commits = commits.ranked();
+    let total: usize = owned.iter().map(|(_, lines)| lines).sum();
+
+    let mut result = format!(
+        "Ownership of {} ({total} lines in {blamed} files",
+        path.display()
+    );
+    if files.len() > MAX_FILES_BLAMED {
+        result.push_str(&format!(", out of {} files", files.len()));
+    }
+    result.push_str(")\n\nBy lines owned now:\n");
+    for (who, lines) in owned.iter().take(MAX_PEOPLE) {
+        let share = *lines as f64 * 100.0 / total.max(1) as f64;
+        result.push_str(&format!("{lines:>8} {share:>5.1}% {who}\n"));
+    }
+    result.push_str(&format!("\nBy commits in the last {days} days:\n"));
+    if commits.is_empty() {
+        result.push_str("    nobody\n");
+    }
+    for (who, count) in commits.iter().take(MAX_PEOPLE) {
+        result.push_str(&format!("{count:>8} {who}\n"));
+    }
+    if !owned.is_empty() {
+        let factor = bus_factor(&owned);
+        let names: Vec<&str> = owned
+            .iter()
+            .take(factor)
+            .map(|(who, _)| who.as_str())
+            .collect();
+        result.push_str(&format!(
+            "\nBus factor: {factor} (more than half of the lines belong to {})\n",
+            names.join(", ")
+        ));
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git shortlog -sn`, and `git blame` summed up
+    pub fn ownership(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: Option<String>,
+            days: Option<u32>,
+        }
+        let Arguments { path, days } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let path = path.unwrap_or_else(|| ".".into());
+        ownership_of_path(Path::new(&path), days.unwrap_or(180))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bus_factor_counts_majority_owners() {
+        let owned = |lines: &[usize]| -> Vec<(String, usize)> {
+            lines.iter().map(|&lines| (String::new(), lines)).collect()
+        };
+        assert_eq!(bus_factor(&owned(&[90, 10])), 1);
+        assert_eq!(bus_factor(&owned(&[50, 30, 20])), 2);
+        assert_eq!(bus_factor(&owned(&[25, 25, 25, 25])), 3);
+        assert_eq!(bus_factor(&owned(&[])), 0);
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn ownership_format() {
+        let output = ownership_of_path(Path::new("src/functions"), 180).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
@@ -0,0 +1,247 @@
+/* spell-checker:words chrono revwalk pickaxe */
+
+use git2::{Blob, DiffOptions, Oid, Patch, Repository, Sort};
+use regex::Regex;
+
+use super::common::pathspec_in_repo;
+
+/// How many lines of each hunk to show around the changed occurrences.
+const MAX_EXCERPT_LINES: usize = 6;
+
+/// How many commits to diff before giving up, as every blob they touch is searched.
+const MAX_COMMITS_SCANNED: usize = 2000;
+
+/// What to look for: a plain string, like `git log -S`, or a pattern, like `git log -G`.
+pub enum Needle {
+    Plain(String),
+    Pattern(Regex),
+}
+
+impl Needle {
+    pub fn new(query: &str, regex: bool) -> Result<Self, String> {
+        if query.is_empty() {
+            return Err("nothing to look for".into());
+        }
+        if regex {
+            Regex::new(query)
+                .map(Needle::Pattern)
+                .map_err(|err| err.to_string())
+        } else {
+            Ok(Needle::Plain(query.to_string()))
+        }
+    }
+
+    fn count(&self, text: &str) -> usize {
+        match self {
+            Needle::Plain(needle) => text.matches(needle.as_str()).count(),
+            Needle::Pattern(pattern) => pattern.find_iter(text).count(),
+        }
+    }
+
+    fn is_in(&self, text: &str) -> bool {
+        match self {
+            Needle::Plain(needle) => text.contains(needle.as_str()),
+            Needle::Pattern(pattern) => pattern.is_match(text),
+        }
+    }
+}
+
+fn count_in_blob(repo: &Repository, id: Oid, needle: &Needle) -> usize {
+    if id.is_zero() {
+        return 0;
+    }
+    repo.find_blob(id)
+        .ok()
+        .filter(|blob: &Blob| !blob.is_binary())
+        .map(|blob| needle.count(&String::from_utf8_lossy(blob.content())))
+        .unwrap_or(0)
+}
+
+/// The lines added or removed in the patch that mention the needle, under their hunk headers.
+fn excerpt(patch: &Patch, needle: &Needle) -> String {
+    let mut result = String::new();
+    for hunk_index in 0..patch.num_hunks() {
+        let Ok((hunk, lines_in_hunk)) = patch.hunk(hunk_index) else {
+            continue;
+        };
+        let mut shown = Vec::new();
+        for line_index in 0..lines_in_hunk {
+            let Ok(line) = patch.line_in_hunk(hunk_index, line_index) else {
+                continue;
+            };
+            let content = String::from_utf8_lossy(line.content());
+            if "+-".contains(line.origin()) && needle.is_in(&content) {
+                shown.push(format!("{}{}", line.origin(), content.trim_end()));
+            }
+        }
+        if shown.is_empty() {
+            continue;
+        }
+        result.push_str(&format!(
+            "    {}\n",
+            String::from_utf8_lossy(hunk.header()).trim_end()
+        ));
+        let hidden = shown.len().saturating_sub(MAX_EXCERPT_LINES);
+        for line in shown.into_iter().take(MAX_EXCERPT_LINES) {
+            result.push_str(&format!("    {line}\n"));
+        }
+        if hidden > 0 {
+            result.push_str(&format!("    … and {hidden} more lines\n"));
+        }
+    }
+    result
+}
+
+/// `git log -S query`: the commits where the number of occurrences changed, newest first.
+pub fn commits_changing_occurrences(
+    needle: &Needle,
+    path: Option<&str>,
+    rev: Option<&str>,
+    max_count: usize,
+) -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let pathspec = pathspec_in_repo(&repo, path)?;
+
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk
+        .set_sorting(Sort::TIME)
+        .map_err(|err| err.to_string())?;
+    match rev {
+        Some(rev) => {
+            let start = repo
+                .revparse_single(rev)
+                .and_then(|object| object.peel_to_commit())
+                .map_err(|err| format!("cannot resolve `{rev}`: {err}"))?;
+            revwalk.push(start.id())
+        }
+        None => revwalk.push_head(),
+    }
+    .map_err(|err| err.to_string())?;
+
+    let mut result = String::new();
+    let mut found = 0;
+    // The next commit to look at, when the scan stopped before the history ran out.
+    let mut cut_off = None;
+    for (scanned, id) in revwalk.enumerate() {
+        let id = id.map_err(|err| err.to_string())?;
+        if scanned == MAX_COMMITS_SCANNED {
+            cut_off = Some(id);
+            break;
+        }
+        let commit = repo.find_commit(id).map_err(|err| err.to_string())?;
+        let tree = commit.tree().map_err(|err| err.to_string())?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(|err| err.to_string())?),
+            Err(_) => None,
+        };
+        let mut options = DiffOptions::new();
+        if let Some(pathspec) = &pathspec {
+            options.pathspec(pathspec);
+        }
+        let diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+            .map_err(|err| err.to_string())?;
+
+        let mut files = String::new();
+        for (index, delta) in diff.deltas().enumerate() {
+            let before = count_in_blob(&repo, delta.old_file().id(), needle);
+            let after = count_in_blob(&repo, delta.new_file().id(), needle);
+            if before == after {
+                continue;
+            }
+            let path = delta
+                .new_file()
+                .path()
+                .or(delta.old_file().path())
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            files.push_str(&format!("  {path}: {before} -> {after} occurrences\n"));
+            if let Ok(Some(patch)) = Patch::from_diff(&diff, index) {
+                files.push_str(&excerpt(&patch, needle));
+            }
+        }
+        if files.is_empty() {
+            continue;
+        }
+
+        found += 1;
+        if found > max_count {
+            result.push_str("more commits match, narrow down the path or the revision\n");
+            break;
+        }
+        let short = commit
+            .as_object()
+            .short_id()
+            .ok()
+            .and_then(|id| id.as_str().map(str::to_string))
+            .unwrap_or_else(|| id.to_string());
+        let date = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let author = commit.author();
+        let author = author.name().unwrap_or_default();
+        let summary = commit.summary().unwrap_or_default();
+        result.push_str(&format!("[{short}] {date} {author} | {summary}\n"));
+        result.push_str(&files);
+    }
+    if result.is_empty() {
+        result.push_str("no commit changed the number of occurrences\n");
+    }
+    if let Some(next) = cut_off {
+        result.push_str(&format!(
+            "stopped after scanning {MAX_COMMITS_SCANNED} commits, pass rev {next} to go on from there, or narrow down the path\n"
+        ));
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git log -S`
+    pub fn pickaxe(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            query: String,
+            regex: Option<bool>,
+            path: Option<String>,
+            rev: Option<String>,
+            max_count: Option<usize>,
+        }
+        let Arguments {
+            query,
+            regex,
+            path,
+            rev,
+            max_count,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let needle = Needle::new(&query, regex.unwrap_or(false))?;
+        let max_count = max_count.unwrap_or(10).clamp(1, 100);
+        commits_changing_occurrences(&needle, path.as_deref(), rev.as_deref(), max_count)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counting() {
+        let plain = Needle::new("strip()", false).unwrap();
+        assert_eq!(plain.count("a.strip() + b.strip()"), 2);
+        let pattern = Needle::new(r"strip\(\w*\)", true).unwrap();
+        assert_eq!(pattern.count("a.strip(x) + b.strip()"), 2);
+        assert!(Needle::new("", false).is_err());
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn pickaxe_format() {
+        let needle = Needle::new("path_in_repo", false).unwrap();
+        let output = commits_changing_occurrences(&needle, Some("src"), None, 5).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
@@ -1,8 +1,11 @@
 use std::io;
+use std::ops::Range;
 use std::path::Path;
 
-use tree_sitter::{Language, Node, Query, QueryCursor};
+use git2::ObjectType;
+use tree_sitter::{Language, Node, Query, QueryCursor, Tree};
 
+use super::at_revision::{entry_at_revision, read_at_revision, Entry};
 use super::common::path_spills_up;
 
 /// Pick a [tree_sitter] parser that would likely
@@ -104,6 +107,16 @@ fn all_matches(query: &Query, node: Node, text_provider: &str) -> Vec<String> {
 ///
 /// If parsing fails, return the unit value `()`.
 fn query_ast_of_file(path: &Path) -> io::Result<String> {
+    let filename_extension = Path::new(&path).extension().and_then(|ext| ext.to_str());
+    if language_for_filename_extension(filename_extension).is_none() {
+        return Ok("()".into());
+    }
+    let source_code = std::fs::read_to_string(path)?;
+    query_ast_of_source(path, &source_code)
+}
+
+/// [query_ast_of_file] for the source code already at hand.
+fn query_ast_of_source(path: &Path, source_code: &str) -> io::Result<String> {
     let filename_extension = Path::new(&path).extension().and_then(|ext| ext.to_str());
     let Some(language) = language_for_filename_extension(filename_extension) else {
         return Ok("()".into());
@@ -113,9 +126,8 @@ fn query_ast_of_file(path: &Path) -> io::Result<String> {
         .set_language(&language)
         .expect("the parser should accept all languages");
 
-    let source_code = std::fs::read_to_string(path)?;
-    let Some(tree) = parser.parse(&source_code, None) else {
-        return Err(io::Error::new(io::ErrorKind::Other, "could not parse"));
+    let Some(tree) = parser.parse(source_code, None) else {
+        return Err(io::Error::other("could not parse"));
     };
     let root_node = tree.root_node();
 
@@ -123,8 +135,8 @@ fn query_ast_of_file(path: &Path) -> io::Result<String> {
         return Ok("()".into());
     };
 
-    let defs = all_matches(&defs, root_node, &source_code);
-    let refs = all_matches(&refs, root_node, &source_code);
+    let defs = all_matches(&defs, root_node, source_code);
+    let refs = all_matches(&refs, root_node, source_code);
 
     let filename_without_extension = path.file_stem().unwrap().to_string_lossy();
     Ok(format!(
@@ -133,6 +145,168 @@ fn query_ast_of_file(path: &Path) -> io::Result<String> {
     ))
 }
 
+/// Outline the file, or the files directly in the directory, as they were at the revision.
+fn query_ast_at_revision(path: &Path, rev: &str) -> io::Result<String> {
+    let entries = match entry_at_revision(rev, path)? {
+        Entry::File(source_code) => return query_ast_of_source(path, &source_code),
+        Entry::Directory(entries) => entries,
+    };
+    let mut result = String::new();
+    for entry in entries {
+        let basename: String = entry.name.chars().flat_map(char::escape_default).collect();
+        match entry.kind {
+            Some(ObjectType::Tree) => result.push_str(&format!("{}/ (directory)", basename)),
+            Some(ObjectType::Commit) => result.push_str(&format!("{}/ (submodule)", basename)),
+            _ => {
+                let entry = path.join(&entry.name);
+                let ext = entry.extension().and_then(|ext| ext.to_str());
+                // Like in the working tree, a file that cannot be outlined does not spoil the rest.
+                let outline = language_for_filename_extension(ext)
+                    .and_then(|_| read_at_revision(rev, &entry).ok())
+                    .and_then(|source_code| query_ast_of_source(&entry, &source_code).ok())
+                    .unwrap_or_else(|| "()".into());
+                result.push_str(&format!("{} {}", basename, outline));
+            }
+        }
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// Parse the source code with a parser picked by the filename extension.
+pub fn parse_source(ext: Option<&str>, source_code: &str) -> Option<Tree> {
+    let language = language_for_filename_extension(ext)?;
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&language)
+        .expect("the parser should accept all languages");
+    parser.parse(source_code, None)
+}
+
+/// Compile whichever of the given queries fits the language
+/// that handles the filename extension.
+fn compile_for_filename_extension(
+    ext: Option<&str>,
+    python: &str,
+    rust: &str,
+    typescript: &str,
+) -> Option<Query> {
+    let language = language_for_filename_extension(ext)?;
+    let source = match ext? {
+        "py" => python,
+        "rs" => rust,
+        _ => typescript,
+    };
+    Some(Query::new(&language, source).expect("the query should compile"))
+}
+
+/// A function-like definition, and where it is in the file.
+#[derive(Clone, Debug)]
+pub struct FunctionSpan {
+    pub name: String,
+    /// One-based, inclusive.
+    pub start_line: usize,
+    /// One-based, inclusive.
+    pub end_line: usize,
+    /// Bytes of the whole definition.
+    pub range: Range<usize>,
+    /// Bytes of the body, if the definition has one.
+    pub body: Option<Range<usize>>,
+}
+
+/// A place where something gets called by name.
+#[derive(Clone, Debug)]
+pub struct CallSite {
+    pub callee: String,
+    /// One-based.
+    pub line: usize,
+    pub byte: usize,
+}
+
+/// Find all the functions and methods defined in the parsed source, in order of appearance.
+pub fn function_spans(ext: Option<&str>, tree: &Tree, source_code: &str) -> Vec<FunctionSpan> {
+    use query_expressions::*;
+
+    let Some(query) = compile_for_filename_extension(
+        ext,
+        python::FUNCTIONS,
+        rust::FUNCTIONS,
+        typescript::FUNCTIONS,
+    ) else {
+        return Vec::new();
+    };
+    let name_index = query.capture_index_for_name("name");
+    let definition_index = query.capture_index_for_name("definition");
+
+    let mut result = Vec::new();
+    let mut cursor = QueryCursor::new();
+    for m in cursor.matches(&query, tree.root_node(), source_code.as_bytes()) {
+        let capture = |index| m.captures.iter().find(|c| Some(c.index) == index);
+        let (Some(name), Some(definition)) = (capture(name_index), capture(definition_index))
+        else {
+            continue;
+        };
+        let definition = definition.node;
+        let body = definition
+            .child_by_field_name("body")
+            .or_else(|| {
+                definition
+                    .child_by_field_name("value")?
+                    .child_by_field_name("body")
+            })
+            .map(|body| body.byte_range());
+        result.push(FunctionSpan {
+            name: name
+                .node
+                .utf8_text(source_code.as_bytes())
+                .unwrap_or_default()
+                .to_string(),
+            start_line: definition.start_position().row + 1,
+            end_line: definition.end_position().row + 1,
+            range: definition.byte_range(),
+            body,
+        });
+    }
+    result.sort_by_key(|span| span.range.start);
+    result
+}
+
+/// Find all the calls made in the parsed source, in order of appearance.
+pub fn call_sites(ext: Option<&str>, tree: &Tree, source_code: &str) -> Vec<CallSite> {
+    use query_expressions::*;
+
+    let Some(query) =
+        compile_for_filename_extension(ext, python::CALLS, rust::CALLS, typescript::CALLS)
+    else {
+        return Vec::new();
+    };
+
+    let mut result = Vec::new();
+    let mut cursor = QueryCursor::new();
+    for m in cursor.matches(&query, tree.root_node(), source_code.as_bytes()) {
+        for c in m.captures {
+            result.push(CallSite {
+                callee: c
+                    .node
+                    .utf8_text(source_code.as_bytes())
+                    .unwrap_or_default()
+                    .to_string(),
+                line: c.node.start_position().row + 1,
+                byte: c.node.start_byte(),
+            });
+        }
+    }
+    result.sort_by_key(|call| call.byte);
+    result
+}
+
+/// Index of the innermost of the spans that contains the given byte.
+pub fn enclosing_function(spans: &[FunctionSpan], byte: usize) -> Option<usize> {
+    (0..spans.len())
+        .filter(|&index| spans[index].range.contains(&byte))
+        .min_by_key(|&index| spans[index].range.len())
+}
+
 pub mod rpc {
     use super::*;
 
@@ -141,14 +315,18 @@ pub mod rpc {
         #[derive(serde::Deserialize)]
         struct Arguments {
             path: String,
+            rev: Option<String>,
         }
-        let Arguments { path } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+        let Arguments { path, rev } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
 
         let path = Path::new(&path);
         if path_spills_up(path) {
             return Err("cannot read files outside the current directory".into());
         }
-        if path.is_dir() {
+        if let Some(rev) = rev {
+            query_ast_at_revision(path, &rev).map_err(|err| err.to_string())
+        } else if path.is_dir() {
             query_ast_of_directory(path).map_err(|err| err.to_string())
         } else {
             query_ast_of_file(path).map_err(|err| err.to_string())
@@ -170,6 +348,20 @@ mod query_expressions {
         pub const REFS: &str = "
             (identifier) @name
         ";
+
+        pub const FUNCTIONS: &str = "
+            (function_definition
+                name: (identifier) @name) @definition
+        ";
+
+        pub const CALLS: &str = "
+            (call
+                function: (identifier) @name)
+
+            (call
+                function: (attribute
+                    attribute: (identifier) @name))
+        ";
     }
 
     // https://github.com/tree-sitter/tree-sitter-typescript/blob/master/queries/tags.scm
@@ -201,6 +393,33 @@ mod query_expressions {
             (new_expression
                 constructor: (identifier) @name)
         ";
+
+        pub const FUNCTIONS: &str = "
+            (function_declaration
+                name: (identifier) @name) @definition
+
+            (generator_function_declaration
+                name: (identifier) @name) @definition
+
+            (method_definition
+                name: (property_identifier) @name) @definition
+
+            (variable_declarator
+                name: (identifier) @name
+                value: [(arrow_function) (function_expression)]) @definition
+        ";
+
+        pub const CALLS: &str = "
+            (call_expression
+                function: (identifier) @name)
+
+            (call_expression
+                function: (member_expression
+                    property: (property_identifier) @name))
+
+            (new_expression
+                constructor: (identifier) @name)
+        ";
     }
 
     // https://github.com/tree-sitter/tree-sitter-rust/blob/master/queries/tags.scm
@@ -246,13 +465,61 @@ mod query_expressions {
             (macro_invocation
                 macro: (identifier) @name)
         ";
+
+        pub const FUNCTIONS: &str = "
+            (function_item
+                name: (identifier) @name) @definition
+
+            (function_signature_item
+                name: (identifier) @name) @definition
+        ";
+
+        pub const CALLS: &str = "
+            (call_expression
+                function: (identifier) @name)
+
+            (call_expression
+                function: (field_expression
+                    field: (field_identifier) @name))
+
+            (call_expression
+                function: (scoped_identifier
+                    name: (identifier) @name))
+        ";
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod test {
     use super::*;
 
+    #[test]
+    fn queries_compile() {
+        for ext in ["py", "rs", "ts", "tsx"] {
+            assert!(queries_for_filename_extension(Some(ext)).is_some());
+            let tree = parse_source(Some(ext), "").unwrap();
+            function_spans(Some(ext), &tree, "");
+            call_sites(Some(ext), &tree, "");
+        }
+    }
+
+    #[test]
+    fn functions_and_calls() {
+        let source = "fn outer() {\n    inner();\n    self::io::helper(1);\n}\n\nfn inner() {}\n";
+        let tree = parse_source(Some("rs"), source).unwrap();
+        let spans = function_spans(Some("rs"), &tree, source);
+        let names: Vec<_> = spans.iter().map(|span| span.name.as_str()).collect();
+        assert_eq!(names, ["outer", "inner"]);
+        assert_eq!((spans[0].start_line, spans[0].end_line), (1, 4));
+
+        let calls = call_sites(Some("rs"), &tree, source);
+        let callees: Vec<_> = calls.iter().map(|call| call.callee.as_str()).collect();
+        assert_eq!(callees, ["inner", "helper"]);
+        let caller = enclosing_function(&spans, calls[1].byte).unwrap();
+        assert_eq!(spans[caller].name, "outer");
+    }
+
     #[test]
     #[ignore = "run manually to see output"]
     fn abstract_syntax_tree() {
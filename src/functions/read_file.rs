@@ -1,10 +1,13 @@
 use std::io;
+use std::ops::Range;
 use std::path::Path;
 
+use super::at_revision::read_at_revision;
 use super::common::path_spills_up;
+use super::query_ast::{function_spans, parse_source};
 
-/// `< path`
-pub fn read_file_with_path(path: &Path) -> io::Result<String> {
+/// Refuse to read anything outside the current directory.
+fn check_confinement(path: &Path) -> io::Result<()> {
     if path.is_absolute() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
@@ -17,10 +20,140 @@ pub fn read_file_with_path(path: &Path) -> io::Result<String> {
             "cannot read files outside the current directory",
         ));
     }
+    Ok(())
+}
 
+/// `< path`
+pub fn read_file_with_path(path: &Path) -> io::Result<String> {
+    check_confinement(path)?;
     std::fs::read_to_string(Path::new(&path))
 }
 
+/// Byte ranges of the function bodies to fold,
+/// leaving alone the ones named in `expand` and whatever contains them.
+fn bodies_to_fold(ext: Option<&str>, source_code: &str, expand: &[String]) -> Vec<Range<usize>> {
+    let Some(tree) = parse_source(ext, source_code) else {
+        return Vec::new();
+    };
+    let spans = function_spans(ext, &tree, source_code);
+    let expanded: Vec<&Range<usize>> = spans
+        .iter()
+        .filter(|span| expand.contains(&span.name))
+        .map(|span| &span.range)
+        .collect();
+
+    let mut result: Vec<Range<usize>> = Vec::new();
+    for span in &spans {
+        let Some(body) = span.body.clone() else {
+            continue;
+        };
+        let contains_expanded = expanded
+            .iter()
+            .any(|range| body.start <= range.start && range.end <= body.end);
+        let within_folded = result.last().is_some_and(|last| body.start < last.end);
+        if contains_expanded || within_folded || expand.contains(&span.name) {
+            continue;
+        }
+        // A Python docstring is the first statement of the body, so keep that one.
+        let body = match tree
+            .root_node()
+            .descendant_for_byte_range(body.start, body.end)
+        {
+            Some(block) if block.kind() == "block" => {
+                let mut cursor = block.walk();
+                let mut statements = block.named_children(&mut cursor);
+                let docstring = statements.next().filter(|statement| {
+                    statement.kind() == "expression_statement"
+                        && statement
+                            .named_child(0)
+                            .is_some_and(|n| n.kind() == "string")
+                });
+                match (docstring, statements.next()) {
+                    (Some(_), Some(rest)) => rest.start_byte()..body.end,
+                    (Some(_), None) => continue,
+                    (None, _) => body,
+                }
+            }
+            _ => body,
+        };
+        result.push(body);
+    }
+    result
+}
+
+/// Number the lines of the source, folding the given byte ranges into `{ … }`.
+fn render_folded(source_code: &str, folds: &[Range<usize>]) -> String {
+    let mut result = String::new();
+    let mut folds = folds.iter().peekable();
+    let mut offset = 0;
+    let mut skip_until = 0;
+    for (index, line) in source_code.split_inclusive('\n').enumerate() {
+        let range = offset..offset + line.len();
+        offset += line.len();
+        if range.end <= skip_until {
+            continue;
+        }
+        let mut text = String::new();
+        let mut cursor = range.start.max(skip_until);
+        while let Some(fold) = folds.next_if(|fold| fold.start < range.end) {
+            text.push_str(&source_code[cursor..fold.start]);
+            let folded = &source_code[fold.clone()];
+            text.push_str(if folded.starts_with('{') {
+                "{ … }"
+            } else {
+                "…"
+            });
+            cursor = fold.end;
+            skip_until = fold.end;
+        }
+        if cursor < range.end {
+            text.push_str(&source_code[cursor..range.end]);
+        } else {
+            // The rest of the line went into the fold, but what follows the fold
+            // on its last line, like a `;` or a `,`, still belongs to the outline.
+            let rest_end = source_code[cursor..]
+                .find('\n')
+                .map_or(source_code.len(), |newline| cursor + newline);
+            text.push_str(&source_code[cursor..rest_end]);
+            skip_until = (rest_end + 1).min(source_code.len());
+        }
+        let text = text.trim_end();
+        if text.is_empty() {
+            result.push_str(&format!("{:>4} |\n", index + 1));
+        } else {
+            result.push_str(&format!("{:>4} | {text}\n", index + 1));
+        }
+    }
+    result
+}
+
+/// `< path`, with function bodies folded to get the shape of the module.
+pub fn read_skeleton_with_path(path: &Path, expand: &[String]) -> io::Result<String> {
+    check_confinement(path)?;
+    let source_code = std::fs::read_to_string(path)?;
+    Ok(skeleton_of_source(path, &source_code, expand))
+}
+
+fn skeleton_of_source(path: &Path, source_code: &str, expand: &[String]) -> String {
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    let folds = bodies_to_fold(ext, source_code, expand);
+    render_folded(source_code, &folds)
+}
+
+/// `git show rev:path`, optionally folded like [read_skeleton_with_path].
+pub fn read_file_at_revision(
+    path: &Path,
+    rev: &str,
+    skeleton: Option<&[String]>,
+) -> io::Result<String> {
+    check_confinement(path)?;
+    let source_code = read_at_revision(rev, path)?;
+    Ok(match skeleton {
+        Some(expand) => skeleton_of_source(path, &source_code, expand),
+        None => source_code,
+    })
+}
+
 pub mod rpc {
     use super::*;
 
@@ -29,9 +162,52 @@ pub mod rpc {
         #[derive(serde::Deserialize)]
         struct Arguments {
             path: String,
+            skeleton: Option<bool>,
+            expand: Option<Vec<String>>,
+            rev: Option<String>,
         }
-        let Arguments { path } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+        let Arguments {
+            path,
+            skeleton,
+            expand,
+            rev,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let path = Path::new(&path);
+        let skeleton = match (skeleton, expand) {
+            (Some(true), expand) | (None, expand @ Some(_)) => Some(expand.unwrap_or_default()),
+            _ => None,
+        };
+        match (rev, skeleton) {
+            (Some(rev), skeleton) => read_file_at_revision(path, &rev, skeleton.as_deref()),
+            (None, Some(expand)) => read_skeleton_with_path(path, &expand),
+            (None, None) => read_file_with_path(path),
+        }
+        .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn folding() {
+        let source = "/// Doc.\nfn one() -> u8 {\n    1\n}\n\nfn two() {\n    one();\n}\n";
+        let folds = bodies_to_fold(Some("rs"), source, &["two".into()]);
+        assert_eq!(
+            render_folded(source, &folds),
+            "   1 | /// Doc.\n   2 | fn one() -> u8 { … }\n   5 |\n   6 | fn two() {\n   7 |     one();\n   8 | }\n"
+        );
+    }
 
-        read_file_with_path(Path::new(&path)).map_err(|err| err.to_string())
+    #[test]
+    fn folding_python() {
+        let source = "def f():\n    \"\"\"Doc.\"\"\"\n    return 1\n\nx = f()\n";
+        let folds = bodies_to_fold(Some("py"), source, &[]);
+        assert_eq!(
+            render_folded(source, &folds),
+            "   1 | def f():\n   2 |     \"\"\"Doc.\"\"\"\n   3 |     …\n   4 |\n   5 | x = f()\n"
+        );
     }
 }
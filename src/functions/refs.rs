@@ -0,0 +1,189 @@
+/* spell-checker:words chrono */
+
+use git2::{BranchType, Commit, Oid, Repository};
+
+/// One branch or tag, and where its tip is.
+struct Ref {
+    name: String,
+    tip: Oid,
+    time: i64,
+    summary: String,
+    /// Counts of commits (ahead, behind) relative to HEAD.
+    against_head: Option<(usize, usize)>,
+    note: String,
+}
+
+fn describe(repo: &Repository, name: String, commit: &Commit, head: Option<Oid>) -> Ref {
+    let against_head = head.and_then(|head| repo.graph_ahead_behind(commit.id(), head).ok());
+    Ref {
+        name,
+        tip: commit.id(),
+        time: commit.time().seconds(),
+        summary: commit.summary().unwrap_or_default().to_string(),
+        against_head,
+        note: String::new(),
+    }
+}
+
+fn render(repo: &Repository, title: &str, mut refs: Vec<Ref>, result: &mut String) {
+    if refs.is_empty() {
+        return;
+    }
+    // The freshest first, so that the stale ones sink to the bottom.
+    refs.sort_by_key(|r| std::cmp::Reverse(r.time));
+    result.push_str(&format!("{title}:\n"));
+    for r in refs {
+        let short = repo
+            .find_object(r.tip, None)
+            .and_then(|object| object.short_id())
+            .ok()
+            .and_then(|id| id.as_str().map(str::to_string))
+            .unwrap_or_else(|| r.tip.to_string());
+        let date = chrono::DateTime::from_timestamp(r.time, 0)
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let position = match r.against_head {
+            Some((0, 0)) => " (at HEAD)".to_string(),
+            Some((ahead, behind)) => format!(" (+{ahead} -{behind} against HEAD)"),
+            None => String::new(),
+        };
+        result.push_str(&format!(
+            "    {} [{short}] {date}{position}{} | {}\n",
+            r.name, r.note, r.summary
+        ));
+    }
+    result.push('\n');
+}
+
+/// `git branch -avv` and `git tag`, with how far each one is from HEAD.
+pub fn list_refs_in_current_repo(
+    kind: Option<&str>,
+    pattern: Option<&str>,
+) -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let head = repo.head().ok().and_then(|head| head.target());
+    let wanted = |name: &str| pattern.is_none_or(|pattern| name.contains(pattern));
+
+    if let Some(kind) = kind {
+        if !["branches", "remotes", "tags"].contains(&kind) {
+            return Err(format!(
+                "unknown kind `{kind}`, expected branches, remotes or tags"
+            ));
+        }
+    }
+    let want_branches = kind.is_none_or(|kind| kind == "branches");
+    let want_remotes = kind.is_none_or(|kind| kind == "remotes");
+    let want_tags = kind.is_none_or(|kind| kind == "tags");
+
+    let (mut local, mut remote, mut tags) = (Vec::new(), Vec::new(), Vec::new());
+
+    for branch in repo.branches(None).map_err(|err| err.to_string())? {
+        let (branch, branch_type) = branch.map_err(|err| err.to_string())?;
+        let Some(name) = branch.name().ok().flatten().map(str::to_string) else {
+            continue;
+        };
+        if !wanted(&name) || name.ends_with("/HEAD") {
+            continue;
+        }
+        let Ok(commit) = branch.get().peel_to_commit() else {
+            continue;
+        };
+        match branch_type {
+            BranchType::Local if want_branches => {
+                let merge_key = format!("branch.{name}.merge");
+                let mut r = describe(&repo, name, &commit, head);
+                if branch.is_head() {
+                    r.name = format!("* {}", r.name);
+                }
+                if let Ok(upstream) = branch.upstream() {
+                    let upstream_name = upstream.name().ok().flatten().unwrap_or_default();
+                    let counts = upstream
+                        .get()
+                        .target()
+                        .and_then(|tip| repo.graph_ahead_behind(commit.id(), tip).ok());
+                    r.note = match counts {
+                        Some((0, 0)) => format!(" [tracks {upstream_name}, up to date]"),
+                        Some((ahead, behind)) => {
+                            format!(" [tracks {upstream_name}, +{ahead} -{behind}]")
+                        }
+                        None => format!(" [tracks {upstream_name}]"),
+                    };
+                } else if let Ok(merge) = repo
+                    .config()
+                    .and_then(|config| config.get_string(&merge_key))
+                {
+                    // Configured, but the remote branch was deleted since.
+                    r.note = format!(" [tracks {merge}, gone]");
+                }
+                local.push(r);
+            }
+            BranchType::Remote if want_remotes => {
+                remote.push(describe(&repo, name, &commit, head));
+            }
+            _ => {}
+        }
+    }
+
+    if want_tags {
+        let names = repo.tag_names(None).map_err(|err| err.to_string())?;
+        for name in names.iter().flatten() {
+            if !wanted(name) {
+                continue;
+            }
+            let Ok(object) = repo.revparse_single(&format!("refs/tags/{name}")) else {
+                continue;
+            };
+            let Ok(commit) = object.peel_to_commit() else {
+                continue;
+            };
+            let mut r = describe(&repo, name.to_string(), &commit, head);
+            if let Some(message) = object.as_tag().and_then(|tag| tag.message()) {
+                let message = message.lines().next().unwrap_or_default();
+                if !message.is_empty() {
+                    r.note = format!(" \"{message}\"");
+                }
+            }
+            tags.push(r);
+        }
+    }
+
+    let mut result = String::new();
+    render(&repo, "Local branches", local, &mut result);
+    render(&repo, "Remote-tracking branches", remote, &mut result);
+    render(&repo, "Tags", tags, &mut result);
+    if result.is_empty() {
+        result.push_str("no refs match\n");
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git branch -avv; git tag`
+    pub fn list_refs(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            kind: Option<String>,
+            pattern: Option<String>,
+        }
+        let Arguments { kind, pattern } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        list_refs_in_current_repo(kind.as_deref(), pattern.as_deref())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn refs_listing_format() {
+        let output = list_refs_in_current_repo(None, None).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
@@ -0,0 +1,164 @@
+use git2::{Commit, DiffFindOptions, DiffOptions, Oid, Repository, RevparseMode};
+
+use super::common::pathspec_in_repo;
+use super::show_commit::{patch_of_diff, stat_of_diff};
+
+/// How many of the commits in between to list before the diff.
+const MAX_COMMITS_LISTED: usize = 20;
+
+fn resolve<'repo>(repo: &'repo Repository, spec: &str) -> Result<Commit<'repo>, String> {
+    repo.revparse_single(spec)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|err| format!("cannot resolve `{spec}`: {err}"))
+}
+
+fn short_id(repo: &Repository, id: Oid) -> String {
+    repo.find_object(id, None)
+        .and_then(|object| object.short_id())
+        .ok()
+        .and_then(|id| id.as_str().map(str::to_string))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// `git diff from..to`, or `git diff from...to` when diffing against the merge base.
+/// A single revision is compared with HEAD.
+pub fn diff_between_revisions(
+    from: &str,
+    to: Option<&str>,
+    merge_base: bool,
+    path: Option<&str>,
+    stat: bool,
+) -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+
+    // Let `from` carry the whole range, the way it would be written on the command line.
+    let (from, to, merge_base) = match to {
+        Some(to) => (resolve(&repo, from)?, resolve(&repo, to)?, merge_base),
+        None => {
+            let spec = repo
+                .revparse(from)
+                .map_err(|err| format!("cannot resolve `{from}`: {err}"))?;
+            let peel = |object: Option<&git2::Object>| match object {
+                Some(object) => resolve(&repo, &object.id().to_string()),
+                None => resolve(&repo, "HEAD"),
+            };
+            let range_from = peel(spec.from())?;
+            let range_to = if spec.mode().contains(RevparseMode::SINGLE) {
+                resolve(&repo, "HEAD")?
+            } else {
+                peel(spec.to())?
+            };
+            let three_dots = spec.mode().contains(RevparseMode::MERGE_BASE);
+            (range_from, range_to, merge_base || three_dots)
+        }
+    };
+
+    let base = if merge_base {
+        let id = repo
+            .merge_base(from.id(), to.id())
+            .map_err(|err| format!("no merge base: {err}"))?;
+        repo.find_commit(id).map_err(|err| err.to_string())?
+    } else {
+        from.clone()
+    };
+
+    let mut result = format!(
+        "Comparing {} with {}",
+        short_id(&repo, base.id()),
+        short_id(&repo, to.id())
+    );
+    if merge_base {
+        result.push_str(&format!(
+            " (merge base of {} and {})",
+            short_id(&repo, from.id()),
+            short_id(&repo, to.id())
+        ));
+    }
+    result.push_str("\n\n");
+
+    let mut revwalk = repo.revwalk().map_err(|err| err.to_string())?;
+    revwalk.push(to.id()).map_err(|err| err.to_string())?;
+    revwalk.hide(base.id()).map_err(|err| err.to_string())?;
+    let commits: Vec<Oid> = revwalk.filter_map(Result::ok).collect();
+    result.push_str(&format!("{} commits:\n", commits.len()));
+    for &id in commits.iter().take(MAX_COMMITS_LISTED) {
+        let summary = repo
+            .find_commit(id)
+            .map(|commit| commit.summary().unwrap_or_default().to_string())
+            .unwrap_or_default();
+        result.push_str(&format!("    [{}] {summary}\n", short_id(&repo, id)));
+    }
+    if commits.len() > MAX_COMMITS_LISTED {
+        result.push_str(&format!(
+            "    … and {} more\n",
+            commits.len() - MAX_COMMITS_LISTED
+        ));
+    }
+    result.push('\n');
+
+    let mut options = DiffOptions::new();
+    if let Some(pathspec) = pathspec_in_repo(&repo, path)? {
+        options.pathspec(pathspec);
+    }
+    let mut diff = repo
+        .diff_tree_to_tree(
+            Some(&base.tree().map_err(|err| err.to_string())?),
+            Some(&to.tree().map_err(|err| err.to_string())?),
+            Some(&mut options),
+        )
+        .map_err(|err| err.to_string())?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true)))
+        .map_err(|err| err.to_string())?;
+
+    result.push_str(&stat_of_diff(&diff)?);
+    if !stat {
+        result.push('\n');
+        result.push_str(&patch_of_diff(&diff)?);
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git diff main...feature`
+    pub fn diff_revisions(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            from: String,
+            to: Option<String>,
+            merge_base: Option<bool>,
+            path: Option<String>,
+            stat: Option<bool>,
+        }
+        let Arguments {
+            from,
+            to,
+            merge_base,
+            path,
+            stat,
+        } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        diff_between_revisions(
+            &from,
+            to.as_deref(),
+            merge_base.unwrap_or(false),
+            path.as_deref(),
+            stat.unwrap_or(false),
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn revision_diff_format() {
+        let output = diff_between_revisions("HEAD~3..HEAD", None, false, None, true).unwrap();
+        println!("{}", output);
+        assert!(false);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::chunks::{chunks_of_source, Chunk};
+use super::common::{cache_file, content_hash, path_spills_up, walk_files};
+use crate::{env, openai};
+
+/// Files larger than this are most likely generated, and not worth indexing.
+const MAX_FILE_SIZE: u64 = 1 << 20;
+
+/// Chunks are cut to this many characters before embedding, to stay within the model limits.
+const MAX_CHARS_PER_INPUT: usize = 8000;
+
+/// How many chunks to send in a single request.
+const INPUTS_PER_REQUEST: usize = 64;
+
+#[derive(Serialize, Deserialize)]
+struct EmbeddedChunk {
+    chunk: Chunk,
+    vector: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EmbeddedFile {
+    hash: u64,
+    chunks: Vec<EmbeddedChunk>,
+}
+
+/// Vectors for every chunk of every file, persisted between runs.
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    model: String,
+    files: HashMap<PathBuf, EmbeddedFile>,
+}
+
+/// What gets embedded for a chunk: where it is, and what it says.
+fn input_for_chunk(file: &Path, chunk: &Chunk, source_code: &str) -> String {
+    let name = chunk.name.as_deref().unwrap_or_default();
+    let mut input = format!("{} {name}\n{}", file.display(), chunk.text(source_code));
+    if input.len() > MAX_CHARS_PER_INPUT {
+        let mut end = MAX_CHARS_PER_INPUT;
+        while !input.is_char_boundary(end) {
+            end -= 1;
+        }
+        input.truncate(end);
+    }
+    input
+}
+
+fn cosine_similarity(one: &[f32], another: &[f32]) -> f32 {
+    let dot: f32 = one.iter().zip(another).map(|(a, b)| a * b).sum();
+    let norm = |vector: &[f32]| vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(one) * norm(another);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+impl Index {
+    /// Load the index from the disk, and embed whatever changed since.
+    async fn load_and_refresh(chat: &openai::Chat, model: &str) -> Result<Self, String> {
+        let location = cache_file("semantic").map_err(|err| err.to_string())?;
+        let mut index: Index = std::fs::read(&location)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .filter(|index: &Index| index.model == model)
+            .unwrap_or_else(|| Index {
+                model: model.to_string(),
+                files: HashMap::new(),
+            });
+
+        let mut stale = Vec::new();
+        let mut present = HashSet::new();
+        for file in walk_files(Path::new(".")).map_err(|err| err.to_string())? {
+            let Ok(metadata) = std::fs::metadata(&file) else {
+                continue;
+            };
+            if metadata.len() > MAX_FILE_SIZE {
+                continue;
+            }
+            let Ok(source_code) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            let hash = content_hash(source_code.as_bytes());
+            present.insert(file.clone());
+            if index
+                .files
+                .get(&file)
+                .is_none_or(|embedded| embedded.hash != hash)
+            {
+                stale.push((file, hash, source_code));
+            }
+        }
+        let before = index.files.len();
+        index.files.retain(|file, _| present.contains(file));
+        let mut changed = index.files.len() != before;
+
+        // Embed the chunks of all the changed files in as few requests as possible.
+        let mut pending: Vec<(usize, Chunk, String)> = Vec::new();
+        let mut remaining: Vec<usize> = stale.iter().map(|_| 0).collect();
+        for (position, (file, _, source_code)) in stale.iter().enumerate() {
+            let ext = file.extension().and_then(|ext| ext.to_str());
+            for chunk in chunks_of_source(ext, source_code) {
+                let input = input_for_chunk(file, &chunk, source_code);
+                pending.push((position, chunk, input));
+                remaining[position] += 1;
+            }
+        }
+        let mut embedded: Vec<Vec<EmbeddedChunk>> = stale.iter().map(|_| Vec::new()).collect();
+        for (position, (file, hash, _)) in stale.iter().enumerate() {
+            if remaining[position] == 0 {
+                let chunks = Vec::new();
+                index.files.insert(
+                    file.clone(),
+                    EmbeddedFile {
+                        hash: *hash,
+                        chunks,
+                    },
+                );
+                changed = true;
+            }
+        }
+        if changed {
+            index.save(&location)?;
+        }
+        // Save after every request, so that a failure halfway keeps what was already embedded.
+        for batch in pending.chunks(INPUTS_PER_REQUEST) {
+            let inputs: Vec<String> = batch.iter().map(|(_, _, input)| input.clone()).collect();
+            let vectors = chat
+                .embed(model, &inputs)
+                .await
+                .map_err(|err| err.to_string())?;
+            for ((position, chunk, _), vector) in batch.iter().zip(vectors) {
+                embedded[*position].push(EmbeddedChunk {
+                    chunk: chunk.clone(),
+                    vector,
+                });
+                remaining[*position] -= 1;
+                if remaining[*position] == 0 {
+                    let (file, hash, _) = &stale[*position];
+                    let chunks = std::mem::take(&mut embedded[*position]);
+                    index.files.insert(
+                        file.clone(),
+                        EmbeddedFile {
+                            hash: *hash,
+                            chunks,
+                        },
+                    );
+                }
+            }
+            index.save(&location)?;
+        }
+        Ok(index)
+    }
+
+    /// Write the index where the next run looks for it.
+    fn save(&self, location: &Path) -> Result<(), String> {
+        let bytes = serde_json::to_vec(self).map_err(|err| err.to_string())?;
+        std::fs::write(location, bytes).map_err(|err| err.to_string())
+    }
+
+    /// The chunks nearest to the query vector, nearest first.
+    fn nearest(&self, query: &[f32], within: &Path, limit: usize) -> Vec<(f32, &Path, &Chunk)> {
+        let mut scored: Vec<(f32, &Path, &Chunk)> = self
+            .files
+            .iter()
+            .filter(|(file, _)| file.starts_with(within))
+            .flat_map(|(file, embedded)| {
+                embedded.chunks.iter().map(move |chunk| {
+                    let similarity = cosine_similarity(query, &chunk.vector);
+                    (similarity, file.as_path(), &chunk.chunk)
+                })
+            })
+            .collect();
+        scored.sort_by(|one, another| another.0.total_cmp(&one.0));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+/// Rank the chunks of the codebase by how close in meaning they are to the query.
+async fn search_with_query(query: &str, within: &Path, limit: usize) -> Result<String, String> {
+    let (api_base, _, secret) = env::vars();
+    let api_base = api_base.as_deref().unwrap_or(env::DEFAULT_API_BASE);
+    let model = env::embedding_model_name_from_env();
+    let model = model.as_deref().unwrap_or("text-embedding-3-small");
+    let chat = openai::Chat::new(api_base, secret.as_deref()).map_err(|err| err.to_string())?;
+
+    let index = Index::load_and_refresh(&chat, model).await?;
+    let query_vector = chat
+        .embed(model, &[query.to_string()])
+        .await
+        .map_err(|err| err.to_string())?
+        .pop()
+        .unwrap_or_default();
+
+    let within = within.strip_prefix(".").unwrap_or(within);
+    let mut result = String::new();
+    for (similarity, file, chunk) in index.nearest(&query_vector, within, limit) {
+        let name = chunk
+            .name
+            .as_ref()
+            .map(|name| format!(" `{name}`"))
+            .unwrap_or_default();
+        result.push_str(&format!(
+            "{}:{}-{}{name} (similarity {similarity:.3})\n",
+            file.display(),
+            chunk.start_line,
+            chunk.end_line
+        ));
+        if let Ok(source_code) = std::fs::read_to_string(file) {
+            let lines = chunk
+                .text(&source_code)
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .take(3);
+            for (offset, line) in lines {
+                let number = chunk.start_line + offset;
+                result.push_str(&format!("{number:>6} | {}\n", line.trim_end()));
+            }
+        }
+    }
+    if result.is_empty() {
+        result.push_str("nothing indexed\n");
+    }
+    Ok(result)
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `rg`, but by meaning
+    pub fn semantic_search(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            query: String,
+            path: Option<String>,
+            limit: Option<usize>,
+        }
+        let Arguments { query, path, limit } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let path = PathBuf::from(path.unwrap_or_else(|| ".".into()));
+        if path.is_absolute() || path_spills_up(&path) {
+            return Err("cannot read files outside the current directory".into());
+        }
+        let limit = limit.unwrap_or(10).clamp(1, 50);
+
+        // The functions are called from within the conversation loop,
+        // so borrow its runtime instead of spinning up another one.
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(search_with_query(&query, &path, limit))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+}
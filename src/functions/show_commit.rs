@@ -1,56 +1,199 @@
 /* spell-checker:words chrono */
 
 use chrono::DateTime;
-use git2::{DiffFormat, DiffOptions, Oid, Repository};
+use git2::{Delta, Diff, DiffFormat, DiffOptions, Patch, Repository};
 
-pub fn show_commit_with_hash(hash: &str) -> Result<String, String> {
+use super::common::pathspec_in_repo;
+
+/// Render the diff the way `git diff` would.
+pub fn patch_of_diff(diff: &Diff) -> Result<String, String> {
+    let mut patch = String::new();
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        let mut origin = String::new();
+        if "+ -".contains(line.origin()) {
+            origin.push(line.origin());
+        }
+        patch.push_str(&format!(
+            "{}{}",
+            origin,
+            String::from_utf8_lossy(line.content())
+        ));
+        true
+    })
+    .map_err(|err| err.to_string())?;
+    Ok(patch)
+}
+
+/// Render the diff the way `git diff --stat` would, one file per line.
+pub fn stat_of_diff(diff: &Diff) -> Result<String, String> {
+    let mut result = String::new();
+    let (mut total_additions, mut total_deletions) = (0, 0);
+    for (index, delta) in diff.deltas().enumerate() {
+        let (additions, deletions) = match Patch::from_diff(diff, index) {
+            Ok(Some(patch)) => {
+                let (_, additions, deletions) =
+                    patch.line_stats().map_err(|err| err.to_string())?;
+                (additions, deletions)
+            }
+            _ => (0, 0),
+        };
+        total_additions += additions;
+        total_deletions += deletions;
+
+        let status = match delta.status() {
+            Delta::Added | Delta::Untracked => 'A',
+            Delta::Deleted => 'D',
+            Delta::Renamed => 'R',
+            Delta::Copied => 'C',
+            Delta::Typechange => 'T',
+            Delta::Conflicted => 'U',
+            _ => 'M',
+        };
+        let old_path = delta
+            .old_file()
+            .path()
+            .map(|path| path.display().to_string());
+        let new_path = delta
+            .new_file()
+            .path()
+            .map(|path| path.display().to_string());
+        let path = match (old_path, new_path) {
+            (Some(old), Some(new)) if old != new => format!("{old} -> {new}"),
+            (_, Some(path)) | (Some(path), None) => path,
+            (None, None) => String::new(),
+        };
+        let binary = if delta.flags().is_binary() {
+            " (binary)"
+        } else {
+            ""
+        };
+        result.push_str(&format!(
+            "{status} {path} | +{additions} -{deletions}{binary}\n"
+        ));
+    }
+    result.push_str(&format!(
+        "{} files changed, {total_additions} insertions(+), {total_deletions} deletions(-)\n",
+        diff.deltas().len()
+    ));
+    Ok(result)
+}
+
+/// Like [patch_of_diff], but cutting the patch of every file to so many lines,
+/// so that a single generated file does not drown out the rest of the change.
+pub fn truncated_patch_of_diff(diff: &Diff, max_lines_per_file: usize) -> Result<String, String> {
+    let mut result = String::new();
+    for index in 0..diff.deltas().len() {
+        let Some(mut patch) = Patch::from_diff(diff, index).map_err(|err| err.to_string())? else {
+            continue;
+        };
+        let buf = patch.to_buf().map_err(|err| err.to_string())?;
+        let text = String::from_utf8_lossy(&buf);
+        let lines: Vec<&str> = text.lines().collect();
+        for line in lines.iter().take(max_lines_per_file) {
+            result.push_str(line);
+            result.push('\n');
+        }
+        if lines.len() > max_lines_per_file {
+            result.push_str(&format!(
+                "… {} more lines of this file are not shown\n",
+                lines.len() - max_lines_per_file
+            ));
+        }
+    }
+    Ok(result)
+}
+
+/// How many lines of the patch of each file to show.
+const MAX_PATCH_LINES_PER_FILE: usize = 400;
+
+/// `git show hash`: the message, the stats and the patch.
+///
+/// A root commit is compared with the empty tree, and a merge with each of its parents
+/// in the stats, while the patch is against the `parent`-th one, the first by default.
+pub fn show_commit_with_hash(
+    hash: &str,
+    parent: Option<usize>,
+    path: Option<&str>,
+) -> Result<String, String> {
     let repo = Repository::discover(".").map_err(|err| err.to_string())?;
 
     let commit = repo
-        .find_commit(Oid::from_str(hash).map_err(|err| err.to_string())?)
+        .revparse_single(hash)
+        .and_then(|object| object.peel_to_commit())
         .map_err(|err| err.to_string())?;
 
     let date = DateTime::from_timestamp(commit.time().seconds(), 0)
         .map(|dt| dt.to_rfc3339())
         .unwrap_or_default();
     let hash = commit.id().to_string();
-    let summary = commit.summary().unwrap_or_default();
     let author = commit.author();
     let author_name = author.name().unwrap_or_default();
     let author_email = author.email().unwrap_or_default();
 
     let mut result = String::new();
+    result.push_str(&format!("Commit: {}\n", hash));
+    if commit.parent_count() > 1 {
+        let parents: Vec<String> = commit.parent_ids().map(|id| id.to_string()).collect();
+        result.push_str(&format!("Merge: {}\n", parents.join(" ")));
+    }
     result.push_str(&format!(
-        "Commit: {}\nAuthor: {} <{}>\nDate: {}\n\n    {}\n\n",
-        hash, author_name, author_email, date, summary
+        "Author: {} <{}>\nDate: {}\n\n",
+        author_name, author_email, date
     ));
+    for line in commit.message().unwrap_or_default().trim_end().lines() {
+        result.push_str(format!("    {line}").trim_end());
+        result.push('\n');
+    }
+    result.push('\n');
 
-    let parent = commit.parent(0).map_err(|err| err.to_string())?;
-    let mut diff_opts = DiffOptions::new();
-    let diff = repo
-        .diff_tree_to_tree(
-            Some(&parent.tree().map_err(|err| err.to_string())?),
-            Some(&commit.tree().map_err(|err| err.to_string())?),
-            Some(&mut diff_opts),
-        )
-        .map_err(|err| err.to_string())?;
+    let tree = commit.tree().map_err(|err| err.to_string())?;
+    let diff_against = |parent_tree: Option<&git2::Tree>| {
+        let mut diff_opts = DiffOptions::new();
+        if let Some(path) = path {
+            diff_opts.pathspec(path);
+        }
+        let mut diff = repo
+            .diff_tree_to_tree(parent_tree, Some(&tree), Some(&mut diff_opts))
+            .map_err(|err| err.to_string())?;
+        diff.find_similar(None).map_err(|err| err.to_string())?;
+        Ok::<_, String>(diff)
+    };
 
-    let mut patch = String::new();
-    diff.print(DiffFormat::Patch, |_, _, line| {
-        let mut origin = String::new();
-        if "+ -".contains(line.origin()) {
-            origin.push(line.origin());
+    let parents: Vec<_> = commit.parents().collect();
+    let shown_parent = parent.unwrap_or(1);
+    let diff = if parents.is_empty() {
+        result.push_str("(root commit, compared with the empty tree)\n");
+        let diff = diff_against(None)?;
+        result.push_str(&stat_of_diff(&diff)?);
+        diff
+    } else {
+        if shown_parent == 0 || shown_parent > parents.len() {
+            return Err(format!("the commit has {} parents", parents.len()));
         }
-        patch.push_str(&format!(
-            "{}{}",
-            origin,
-            String::from_utf8_lossy(line.content())
-        ));
-        true
-    })
-    .map_err(|err| err.to_string())?;
+        let mut shown = None;
+        for (index, parent) in parents.iter().enumerate() {
+            let parent_tree = parent.tree().map_err(|err| err.to_string())?;
+            let diff = diff_against(Some(&parent_tree))?;
+            if parents.len() > 1 {
+                result.push_str(&format!(
+                    "Against parent {} ({}):\n",
+                    index + 1,
+                    parent.id()
+                ));
+            }
+            result.push_str(&stat_of_diff(&diff)?);
+            if index + 1 == shown_parent {
+                shown = Some(diff);
+            }
+        }
+        if parents.len() > 1 {
+            result.push_str(&format!("\nThe patch against parent {shown_parent}:\n"));
+        }
+        shown.expect("the parent was checked to be in range")
+    };
+    result.push('\n');
 
-    result.push_str(&patch);
+    result.push_str(&truncated_patch_of_diff(&diff, MAX_PATCH_LINES_PER_FILE)?);
 
     Ok(result)
 }
@@ -58,13 +201,37 @@ pub fn show_commit_with_hash(hash: &str) -> Result<String, String> {
 pub mod rpc {
     use super::*;
 
+    /// `git show`
     pub fn show_commit(arguments: &str) -> Result<String, String> {
         #[derive(serde::Deserialize)]
         struct Arguments {
             hash: String,
+            parent: Option<usize>,
+            path: Option<String>,
         }
-        let Arguments { hash } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+        let Arguments { hash, parent, path } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+        let pathspec = pathspec_in_repo(&repo, path.as_deref())?;
+        show_commit_with_hash(&hash, parent, pathspec.as_deref())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
 
-        show_commit_with_hash(&hash).map_err(|err| err.to_string())
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn root_commit_format() {
+        let repo = Repository::discover(".").unwrap();
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        let root = revwalk.last().unwrap().unwrap();
+        let output = show_commit_with_hash(&root.to_string(), None, Some("src/env.rs")).unwrap();
+        println!("{}", output);
+        assert!(false);
     }
 }
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use git2::{DiffOptions, IndexConflict, Patch, Repository, Status, StatusOptions};
+
+use super::common::pathspec_in_repo;
+use super::show_commit::{patch_of_diff, stat_of_diff};
+
+/// How `git status` calls the conflict, by which sides still have the file.
+pub fn conflict_kind(conflict: &IndexConflict) -> &'static str {
+    match (
+        conflict.ancestor.is_some(),
+        conflict.our.is_some(),
+        conflict.their.is_some(),
+    ) {
+        (true, true, true) => "both modified",
+        (false, true, true) => "both added",
+        (true, true, false) => "deleted by them",
+        (true, false, true) => "deleted by us",
+        (false, true, false) => "added by us",
+        (false, false, true) => "added by them",
+        (true, false, false) | (false, false, false) => "both deleted",
+    }
+}
+
+/// The kind of every conflict in the index, by the path relative to the repository.
+fn conflict_kinds(repo: &Repository) -> Result<HashMap<String, &'static str>, String> {
+    let index = repo.index().map_err(|err| err.to_string())?;
+    let mut kinds = HashMap::new();
+    for conflict in index.conflicts().map_err(|err| err.to_string())? {
+        let conflict = conflict.map_err(|err| err.to_string())?;
+        let entry = [&conflict.our, &conflict.their, &conflict.ancestor]
+            .into_iter()
+            .find_map(Option::as_ref);
+        if let Some(entry) = entry {
+            let path = String::from_utf8_lossy(&entry.path).into_owned();
+            kinds.insert(path, conflict_kind(&conflict));
+        }
+    }
+    Ok(kinds)
+}
+
+/// `git status`, grouped by what state each file is in.
+pub fn status_of_working_tree(path: Option<&str>) -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true)
+        .renames_index_to_workdir(true);
+    if let Some(pathspec) = pathspec_in_repo(&repo, path)? {
+        options.pathspec(pathspec);
+    }
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|err| err.to_string())?;
+
+    let kinds = conflict_kinds(&repo)?;
+    let (mut conflicted, mut staged, mut unstaged, mut untracked) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for entry in statuses.iter() {
+        let status = entry.status();
+        let path = entry.path().unwrap_or_default().to_string();
+        if status.is_conflicted() {
+            let kind = kinds.get(&path).copied().unwrap_or("both modified");
+            conflicted.push(format!("{kind}: {path}"));
+            continue;
+        }
+        let renamed = |diff: Option<git2::DiffDelta>| {
+            let diff = diff?;
+            let old = diff.old_file().path()?.display().to_string();
+            let new = diff.new_file().path()?.display().to_string();
+            Some(format!("{old} -> {new}"))
+        };
+        let staged_change = if status.contains(Status::INDEX_NEW) {
+            Some(format!("new file: {path}"))
+        } else if status.contains(Status::INDEX_DELETED) {
+            Some(format!("deleted: {path}"))
+        } else if status.contains(Status::INDEX_RENAMED) {
+            let renamed = renamed(entry.head_to_index()).unwrap_or_else(|| path.clone());
+            Some(format!("renamed: {renamed}"))
+        } else if status.intersects(Status::INDEX_MODIFIED | Status::INDEX_TYPECHANGE) {
+            Some(format!("modified: {path}"))
+        } else {
+            None
+        };
+        let unstaged_change = if status.contains(Status::WT_NEW) {
+            untracked.push(path.clone());
+            None
+        } else if status.contains(Status::WT_DELETED) {
+            Some(format!("deleted: {path}"))
+        } else if status.contains(Status::WT_RENAMED) {
+            let renamed = renamed(entry.index_to_workdir()).unwrap_or_else(|| path.clone());
+            Some(format!("renamed: {renamed}"))
+        } else if status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE) {
+            Some(format!("modified: {path}"))
+        } else {
+            None
+        };
+        staged.extend(staged_change);
+        unstaged.extend(unstaged_change);
+    }
+
+    let mut result = String::new();
+    let head = repo.head().ok();
+    match head.as_ref().and_then(|head| head.shorthand()) {
+        Some(branch) if repo.head_detached().unwrap_or(false) => {
+            result.push_str(&format!("HEAD detached at {branch}\n"));
+        }
+        Some(branch) => result.push_str(&format!("On branch {branch}\n")),
+        None => result.push_str("No commits yet\n"),
+    }
+    if repo.state() != git2::RepositoryState::Clean {
+        result.push_str(&format!("In progress: {:?}\n", repo.state()));
+    }
+    for (title, entries) in [
+        ("Conflicted", conflicted),
+        ("Staged", staged),
+        ("Not staged", unstaged),
+        ("Untracked", untracked),
+    ] {
+        if entries.is_empty() {
+            continue;
+        }
+        result.push_str(&format!("\n{title}:\n"));
+        for entry in entries {
+            result.push_str(&format!("    {entry}\n"));
+        }
+    }
+    if statuses.is_empty() {
+        result.push_str("\nNothing to commit, the working tree is clean\n");
+    }
+    Ok(result)
+}
+
+/// The untracked files with their sizes, since their content could be of any length.
+fn untracked_files(repo: &Repository, path: Option<&str>) -> Result<String, String> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    if let Some(pathspec) = pathspec_in_repo(repo, path)? {
+        options.pathspec(pathspec);
+    }
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|err| err.to_string())?;
+    let workdir = repo.workdir().ok_or("the repository has no working tree")?;
+
+    let mut result = String::new();
+    for entry in statuses.iter() {
+        if !entry.status().contains(Status::WT_NEW) {
+            continue;
+        }
+        let path = entry.path().unwrap_or_default();
+        let size = std::fs::metadata(workdir.join(path)).map_or(0, |metadata| metadata.len());
+        result.push_str(&format!("    {path} ({size} bytes)\n"));
+    }
+    if !result.is_empty() {
+        result.insert_str(0, "\nUntracked, not shown:\n");
+    }
+    Ok(result)
+}
+
+/// `git diff HEAD` for the whole working tree, or `git diff --cached` for the index only.
+pub fn diff_of_working_tree(
+    staged: bool,
+    path: Option<&str>,
+    stat: bool,
+) -> Result<String, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+
+    let mut options = DiffOptions::new();
+    if let Some(pathspec) = pathspec_in_repo(&repo, path)? {
+        options.pathspec(pathspec);
+    }
+    // An unborn branch has nothing to compare against, so everything is new.
+    let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let diff = if staged {
+        repo.diff_tree_to_index(head.as_ref(), None, Some(&mut options))
+    } else {
+        repo.diff_tree_to_workdir_with_index(head.as_ref(), Some(&mut options))
+    }
+    .map_err(|err| err.to_string())?;
+
+    let mut result = stat_of_diff(&diff)?;
+    if !stat {
+        result.push('\n');
+        result.push_str(&patch_of_diff(&diff)?);
+    }
+    if !staged {
+        result.push_str(&untracked_files(&repo, path)?);
+    }
+    Ok(result)
+}
+
+/// Files whose changes say little about the intent, so only their stats are shown.
+const GENERATED_FILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+];
+
+/// Split the budget of lines between the files, so that small patches are shown whole
+/// and whatever they leave over goes to the larger ones.
+fn share_lines(sizes: &[usize], budget: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| sizes[index]);
+    let mut shares = vec![0; sizes.len()];
+    let mut left = budget;
+    for (done, &index) in order.iter().enumerate() {
+        let fair = left / (sizes.len() - done);
+        shares[index] = sizes[index].min(fair);
+        left -= shares[index];
+    }
+    shares
+}
+
+/// `git diff --cached --stat` and the patch, cut to about `budget` lines in total,
+/// or nothing if nothing is staged.
+pub fn staged_changes(budget: usize) -> Result<Option<String>, String> {
+    let repo = Repository::discover(".").map_err(|err| err.to_string())?;
+    let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let mut diff = repo
+        .diff_tree_to_index(head.as_ref(), None, None)
+        .map_err(|err| err.to_string())?;
+    diff.find_similar(None).map_err(|err| err.to_string())?;
+    if diff.deltas().len() == 0 {
+        return Ok(None);
+    }
+
+    let mut patches = Vec::new();
+    for index in 0..diff.deltas().len() {
+        let Some(mut patch) = Patch::from_diff(&diff, index).map_err(|err| err.to_string())? else {
+            continue;
+        };
+        let generated = patch
+            .delta()
+            .new_file()
+            .path()
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| GENERATED_FILES.contains(&name));
+        if generated {
+            continue;
+        }
+        let buf = patch.to_buf().map_err(|err| err.to_string())?;
+        patches.push(String::from_utf8_lossy(&buf).into_owned());
+    }
+    let sizes: Vec<usize> = patches.iter().map(|patch| patch.lines().count()).collect();
+    let shares = share_lines(&sizes, budget);
+
+    let mut result = stat_of_diff(&diff)?;
+    result.push('\n');
+    for ((patch, size), share) in patches.iter().zip(sizes).zip(shares) {
+        for line in patch.lines().take(share) {
+            result.push_str(line);
+            result.push('\n');
+        }
+        if size > share {
+            result.push_str(&format!(
+                "… {} more lines of this file are not shown\n",
+                size - share
+            ));
+        }
+    }
+    Ok(Some(result))
+}
+
+pub mod rpc {
+    use super::*;
+
+    /// `git status`
+    pub fn show_status(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            path: Option<String>,
+        }
+        let Arguments { path } = serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        status_of_working_tree(path.as_deref())
+    }
+
+    /// `git diff HEAD`
+    pub fn show_working_diff(arguments: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct Arguments {
+            staged: Option<bool>,
+            path: Option<String>,
+            stat: Option<bool>,
+        }
+        let Arguments { staged, path, stat } =
+            serde_json::from_str(arguments).map_err(|err| err.to_string())?;
+
+        diff_of_working_tree(
+            staged.unwrap_or(false),
+            path.as_deref(),
+            stat.unwrap_or(false),
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small_patches_are_shown_whole() {
+        assert_eq!(share_lines(&[10, 500, 1000], 300), vec![10, 145, 145]);
+        assert_eq!(share_lines(&[10, 20], 300), vec![10, 20]);
+        assert_eq!(share_lines(&[], 300), Vec::<usize>::new());
+    }
+
+    #[test]
+    #[ignore = "run manually to see output"]
+    fn status_format() {
+        println!("{}", status_of_working_tree(None).unwrap());
+        println!("{}", diff_of_working_tree(false, None, false).unwrap());
+        assert!(false);
+    }
+}
@@ -3,16 +3,21 @@ use colored::Colorize;
 use crate::openai::ToolCallRequest;
 
 mod throbber;
-pub use throbber::start_throbber;
+pub use throbber::{set_throbber_status, start_throbber};
 
-/// Show the model's reply to the user.
-pub fn show_reply(content: &str, tool_calls: &[ToolCallRequest]) {
+/// Show which functions the model asked to call.
+fn show_calls(tool_calls: &[ToolCallRequest]) {
     for call in tool_calls {
         let call_notch = "<<".bright_cyan().dimmed().bold();
         let call_name = call.function.name.cyan();
         let call_arguments = call.function.arguments.cyan();
         eprintln!("{} {}{}", call_notch, call_name, call_arguments);
     }
+}
+
+/// Show the model's reply to the user.
+pub fn show_reply(content: &str, tool_calls: &[ToolCallRequest]) {
+    show_calls(tool_calls);
 
     if !content.is_empty() {
         let reply_notch = "<<".bright_green().dimmed().bold();
@@ -27,6 +32,38 @@ pub fn show_reply(content: &str, tool_calls: &[ToolCallRequest]) {
     eprintln!();
 }
 
+/// Show a piece of the model's reply as soon as it arrives.
+pub fn show_reply_part(part: &str, first: bool) {
+    if first {
+        let reply_notch = "<<".bright_green().dimmed().bold();
+        eprint!("{} ", reply_notch);
+    }
+    eprint!("{}", part);
+}
+
+/// Finish the reply that was shown piece by piece, with the calls it asked for.
+pub fn show_reply_end(tool_calls: &[ToolCallRequest]) {
+    eprintln!();
+    show_calls(tool_calls);
+    eprintln!();
+}
+
+/// Tell the user the reply shown so far is dropped, as it was cut off at the length limit.
+pub fn show_reply_discarded() {
+    let reply_notch = "<<".bright_green().dimmed().bold();
+    let notice = "(cut off at the length limit, dropped)".dimmed();
+    eprintln!("\n{} {}\n", reply_notch, notice);
+}
+
+/// Show why there is no reply, on a line of its own even if a part of the reply was shown.
+pub fn show_error(error: &str, after_part: bool) {
+    if after_part {
+        eprintln!();
+    }
+    let notch = "!!".bright_red().dimmed().bold();
+    eprintln!("{} {}\n", notch, error.red());
+}
+
 /// Read user input from stdin
 pub fn read_user_input() -> String {
     let user_notch = ">>".bright_yellow().dimmed().bold();
@@ -38,6 +75,24 @@ pub fn read_user_input() -> String {
     input.trim().to_string()
 }
 
+/// Ask the user something outside of the conversation, and return the answer as typed.
+pub fn ask_user(question: &str) -> String {
+    let question_notch = "??".bright_magenta().dimmed().bold();
+    eprint!("{} {} ", question_notch, question);
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).unwrap();
+    eprintln!();
+    input.trim().to_string()
+}
+
+/// Show what a function is about to do, before asking the user whether it may.
+pub fn show_proposal(title: &str, proposal: &str) {
+    let question_notch = "??".bright_magenta().dimmed().bold();
+    eprintln!("{} {}", question_notch, title);
+    eprintln!("{}\n", proposal.trim_end());
+}
+
 /// Show an already read user input.
 pub fn show_user_input(input: &str) {
     let user_notch = ">>".bright_yellow().dimmed().bold();
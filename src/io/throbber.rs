@@ -17,13 +17,32 @@ impl Throbber {
     }
 }
 
+/// What the throbber says next to itself, if anything.
+static STATUS: std::sync::Mutex<String> = std::sync::Mutex::new(String::new());
+
+/// Have the throbber say something next to itself, or nothing once the status is empty.
+/// Without the throbber, the status goes on a line of its own.
+pub fn set_throbber_status(status: &str) {
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        if !status.is_empty() {
+            eprintln!("{status}");
+        }
+        return;
+    }
+    if let Ok(mut current) = STATUS.lock() {
+        *current = status.to_string();
+    }
+}
+
 /// Show a pseudo-textual progress indicator until the given signal is set.
 fn indicate_until_signalled(signal: &Signal) {
     let frames = "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏";
     let mut i = 0;
+    // How much of the line the last frame took, to blank it out.
+    let mut width = 1;
     loop {
         if signal.load(std::sync::atomic::Ordering::Relaxed) {
-            eprint!("\r \r");
+            eprint!("\r{}\r", " ".repeat(width));
             break;
         }
         let frame = frames
@@ -31,7 +50,23 @@ fn indicate_until_signalled(signal: &Signal) {
             .nth(i % frames.len())
             .unwrap_or_default()
             .to_string();
-        eprint!("\r{}", frame.bright_cyan());
+        let status = STATUS
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default();
+        let shown = if status.is_empty() {
+            String::new()
+        } else {
+            format!(" {status}")
+        };
+        let padding = width.saturating_sub(1 + shown.chars().count());
+        eprint!(
+            "\r{}{}{}",
+            frame.bright_cyan(),
+            shown.dimmed(),
+            " ".repeat(padding)
+        );
+        width = 1 + shown.chars().count();
         i += 1;
         std::thread::sleep(std::time::Duration::from_millis(100));
     }
@@ -45,6 +80,7 @@ pub fn start_throbber() -> Throbber {
         return Throbber(None);
     }
 
+    set_throbber_status("");
     let signal = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let other_signal = signal.clone();
     let handle = std::thread::spawn(move || {
@@ -1,3 +1,4 @@
+mod commands;
 mod env;
 mod error;
 mod functions;
@@ -10,13 +11,18 @@ use error::Error;
 async fn main() -> Result<(), Error> {
     use openai::VecOfMessages as _;
 
+    // Some invocations are not a conversation, but a subcommand.
+    if let Some(result) = commands::run(&env::args()).await {
+        return result;
+    }
+
     // Read the secret we will be using either from the environment or from the `.env` file.
     let (api_base, model, secret) = env::vars();
     if api_base.is_none() && secret.is_none() {
         return Err("expected env `OPENAI_API_KEY` to be available".into());
     }
-    let api_base = api_base.as_deref().unwrap_or("https://api.openai.com/v1");
-    let model = model.as_deref().unwrap_or("gpt-4o");
+    let api_base = api_base.as_deref().unwrap_or(env::DEFAULT_API_BASE);
+    let model = model.as_deref().unwrap_or(env::DEFAULT_MODEL);
     let secret = secret.as_deref();
 
     // Pre-populate the conversation with the context prompt.
@@ -35,17 +41,42 @@ async fn main() -> Result<(), Error> {
     eprintln!();
 
     // Converse until the user enters an empty line.
-    let chat = openai::Chat::new(api_base, secret).map_err(|err| err.to_string())?;
+    let chat = openai::Chat::new(api_base, secret)
+        .map_err(|err| err.to_string())?
+        .with_retry_notice(io::set_throbber_status);
     loop {
         steps_since_last_rollup += 1;
 
-        // Generate the next message in the conversation.
-        let little_snake = io::start_throbber();
+        // Generate the next message in the conversation,
+        // showing the text as it arrives, and the throbber until it does.
+        let mut little_snake = Some(io::start_throbber());
+        let mut streamed = false;
         let reply = chat
-            .complete(model, &messages, &openai::all_functions())
-            .await
-            .map_err(|err| err.to_string())?;
-        little_snake.stop();
+            .complete_streaming(model, &messages, &openai::all_functions(), |text| {
+                if let Some(little_snake) = little_snake.take() {
+                    little_snake.stop();
+                }
+                io::show_reply_part(text, !streamed);
+                streamed = true;
+            })
+            .await;
+        if let Some(little_snake) = little_snake {
+            little_snake.stop();
+        }
+        // Once the retries are used up, or the error is not worth retrying,
+        // the conversation so far is kept, and it is up to the user what to do next.
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(err) => {
+                io::show_error(&err.to_string(), streamed);
+                let input = io::read_user_input();
+                if input.is_empty() {
+                    break;
+                }
+                messages.push_user_message(&input);
+                continue;
+            }
+        };
 
         // The model may reply to us with a text,
         // or it may ask us to do something through a tool call.
@@ -56,6 +87,10 @@ async fn main() -> Result<(), Error> {
             return Err(refusal.into());
         }
         if reply.finish_reason == openai::FinishReason::UsageExceeded {
+            // The reply is not kept, so what was shown of it should not look like it is.
+            if streamed {
+                io::show_reply_discarded();
+            }
             if steps_since_last_rollup < 11 {
                 // This is helpless by this point.
                 return Err("usage exceeded".into());
@@ -78,7 +113,11 @@ async fn main() -> Result<(), Error> {
 
         // Record the reply and the function call ids, if there are any.
         messages.push_assistant_message(&content, &calls);
-        io::show_reply(&content, &calls);
+        if streamed {
+            io::show_reply_end(&calls);
+        } else {
+            io::show_reply(&content, &calls);
+        }
 
         // If the model asked us to call a function, do so.
         if !calls.is_empty() {
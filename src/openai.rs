@@ -2,8 +2,10 @@ use serde_json::json;
 
 pub mod error;
 pub mod prompts;
+pub mod retry;
 pub mod rollup;
 pub mod schema;
+pub mod stream;
 
 pub mod vec_of_messages;
 
@@ -14,11 +16,15 @@ pub use self::vec_of_messages::VecOfMessages;
 /// Result with the right error.
 pub type Result<T> = std::result::Result<T, error::OpenAIError>;
 
-/// An HTTP client to the OpenAI Chat Completions API.
+/// Something to tell the user while a request waits to be retried.
+type RetryNotice = Box<dyn Fn(&str) + Send + Sync>;
+
+/// An HTTP client to the OpenAI Chat Completions and Embeddings APIs.
 /// It does not hold any persistent connections, each completion is a new request.
 pub struct Chat {
     base: String,
     client: reqwest::Client,
+    retry_notice: Option<RetryNotice>,
 }
 
 impl Chat {
@@ -39,7 +45,66 @@ impl Chat {
             .default_headers(headers)
             .build()?;
         let base = base.to_string();
-        Ok(Self { base, client })
+        Ok(Self {
+            base,
+            client,
+            retry_notice: None,
+        })
+    }
+
+    /// Have the client say what it is waiting for when a request is being retried.
+    pub fn with_retry_notice(mut self, notice: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.retry_notice = Some(Box::new(notice));
+        self
+    }
+
+    /// Post the body to the endpoint, trying again while the failures look temporary.
+    /// Only the response headers are waited for, so nothing is retried once the body is being read.
+    async fn send<Body>(&self, endpoint: &str, body: &Body) -> Result<reqwest::Response>
+    where
+        Body: serde::Serialize,
+    {
+        let base = &self.base;
+        let address = format!("{base}/{endpoint}");
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let last = attempt >= retry::MAX_ATTEMPTS;
+            let (reason, asked) = match self.client.post(&address).json(body).send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let asked = retry::delay_from_headers(response.headers());
+                    let text = response.text().await?;
+                    let details = serde_json::from_str::<ErroneousCompletionResponse>(&text)
+                        .ok()
+                        .map(|response| response.error);
+                    if last || !retry::is_retryable(status, details.as_ref()) {
+                        return Err(match details {
+                            Some(details) => error::OpenAIError::ProtocolError(details),
+                            None => error::OpenAIError::HttpStatus(status, text),
+                        });
+                    }
+                    (status.to_string(), asked)
+                }
+                Err(err) if !last && retry::is_transient(&err) => (err.to_string(), None),
+                Err(err) => return Err(err.into()),
+            };
+
+            let delay = retry::delay(attempt, asked);
+            if let Some(notice) = &self.retry_notice {
+                notice(&format!(
+                    "{reason}, trying again in {:.0?} ({attempt} of {} attempts failed)",
+                    delay,
+                    retry::MAX_ATTEMPTS
+                ));
+            }
+            tokio::time::sleep(delay).await;
+            if let Some(notice) = &self.retry_notice {
+                notice("");
+            }
+        }
     }
 
     /// Generic call to any of OpenAI API endpoints.
@@ -48,17 +113,7 @@ impl Chat {
         Body: serde::Serialize + std::fmt::Debug,
         Response: for<'re> serde::Deserialize<'re>,
     {
-        let base = &self.base;
-        let address = format!("{base}/{endpoint}");
-
-        let response = self
-            .client
-            .post(address)
-            .json(body)
-            .send()
-            .await?
-            .text()
-            .await?;
+        let response = self.send(endpoint, body).await?.text().await?;
 
         let response: Response = serde_json::from_str(&response).map_err(|err| {
             error::OpenAIError::SchemaMismatch(
@@ -71,24 +126,25 @@ impl Chat {
         Ok(response)
     }
 
-    /// Infer the next message in the conversation.
-    pub async fn complete(
-        &self,
+    /// The request body for a completion.
+    fn completion_body(
         model: &str,
         messages: &[Message],
         tools: &serde_json::Value,
-    ) -> Result<CompletionChoice> {
-        let completion: CompletionResponse = self
-            .call(
-                "chat/completions",
-                &json!({
-                    "model": model,
-                    "messages": messages,
-                    "tools": tools,
-                }),
-            )
-            .await?;
+    ) -> serde_json::Value {
+        let mut body = json!({
+            "model": model,
+            "messages": messages,
+        });
+        // The API refuses an empty list of tools, so leave it out instead.
+        if tools.as_array().is_none_or(|tools| !tools.is_empty()) {
+            body["tools"] = tools.clone();
+        }
+        body
+    }
 
+    /// The choice to go on with, out of the ones in the completion.
+    fn pick_choice(completion: CompletionResponse) -> Result<CompletionChoice> {
         let choices = match completion {
             CompletionResponse::Success(SuccessfulCompletionResponse { choices, .. }) => choices,
             CompletionResponse::Failure(ErroneousCompletionResponse { error }) => {
@@ -104,4 +160,112 @@ impl Chat {
         };
         Ok(choice)
     }
+
+    /// Infer the next message in the conversation.
+    pub async fn complete(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: &serde_json::Value,
+    ) -> Result<CompletionChoice> {
+        let body = Self::completion_body(model, messages, tools);
+        let completion: CompletionResponse = self.call("chat/completions", &body).await?;
+        Self::pick_choice(completion)
+    }
+
+    /// Infer the next message in the conversation, handing out the text as it arrives.
+    ///
+    /// The request is retried only until the reply starts to arrive. After that, a part of it
+    /// may have been handed out already, so a failure is not retried, and comes as [`OpenAIError::Interrupted`].
+    pub async fn complete_streaming(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: &serde_json::Value,
+        mut on_text: impl FnMut(&str),
+    ) -> Result<CompletionChoice> {
+        let mut body = Self::completion_body(model, messages, tools);
+        body["stream"] = json!(true);
+        let mut response = self.send("chat/completions", &body).await?;
+        let mismatch = |got: String, err: serde_json::Error| {
+            error::OpenAIError::SchemaMismatch(
+                serde_json::to_string_pretty(&body).unwrap(),
+                got,
+                err.to_string(),
+            )
+        };
+
+        // Errors, and servers that do not stream, answer with the whole thing at once.
+        let streamed = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !streamed {
+            let text = response.text().await?;
+            let completion = serde_json::from_str(&text).map_err(|err| mismatch(text, err))?;
+            let choice = Self::pick_choice(completion)?;
+            if let Some(content) = &choice.message.content {
+                on_text(content);
+            }
+            return Ok(choice);
+        }
+
+        let mut events = stream::Events::default();
+        let mut assembly = stream::Assembly::default();
+        let reading: Result<()> = async {
+            'reading: while let Some(bytes) = response.chunk().await? {
+                for data in events.push(&bytes) {
+                    if data == "[DONE]" {
+                        break 'reading;
+                    }
+                    let chunk = serde_json::from_str(&data).map_err(|err| mismatch(data, err))?;
+                    let chunk = match chunk {
+                        CompletionChunkResponse::Success(chunk) => chunk,
+                        CompletionChunkResponse::Failure(ErroneousCompletionResponse { error }) => {
+                            return Err(error::OpenAIError::ProtocolError(error));
+                        }
+                    };
+                    if let Some(text) = assembly.apply(chunk) {
+                        on_text(&text);
+                    }
+                }
+            }
+            Ok(())
+        }
+        .await;
+        reading.map_err(|err| error::OpenAIError::Interrupted(Box::new(err)))?;
+        Ok(assembly.finish())
+    }
+
+    /// Turn each of the texts into a vector, in the same order.
+    pub async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        let response: EmbeddingResponse = self
+            .call(
+                "embeddings",
+                &json!({
+                    "model": model,
+                    "input": inputs,
+                }),
+            )
+            .await?;
+
+        let mut data = match response {
+            EmbeddingResponse::Success(SuccessfulEmbeddingResponse { data, .. }) => data,
+            EmbeddingResponse::Failure(ErroneousCompletionResponse { error }) => {
+                return Err(error::OpenAIError::ProtocolError(error));
+            }
+        };
+        if data.len() != inputs.len() {
+            return Err(error::OpenAIError::EmbeddingCountMismatch(
+                inputs.len(),
+                data.len(),
+            ));
+        }
+        data.sort_by_key(|embedding| embedding.index);
+        Ok(data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
 }
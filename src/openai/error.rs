@@ -13,9 +13,18 @@ pub enum OpenAIError {
     #[error("Sent this: {0}\n\nGot this: {1}\n\nWhile Parsing: {2}")]
     SchemaMismatch(String, String, String),
 
+    #[error("HTTP {0}: {1}")]
+    HttpStatus(reqwest::StatusCode, String),
+
     #[error("Protocol error: {0:?}")]
     ProtocolError(ErrorDetails),
 
+    #[error("The reply was cut off: {0}")]
+    Interrupted(Box<OpenAIError>),
+
     #[error("No choices in the completion")]
     NoChoice,
+
+    #[error("Asked for {0} embeddings, got {1}")]
+    EmbeddingCountMismatch(usize, usize),
 }
@@ -17,11 +17,19 @@ If unsure about an answer, request more information from the user.
 When asked about a particular definition, first use the `q` (query) function to find the files \
 which have that definition. Then, use the `F` (read file) function to read them in detail \
 and make sense of their contents.
+For large files, read the skeleton first, then expand only the functions that matter.
 
 When asked about the whole codebase or cross-cutting concerns, \
-start by identifying relevant files with the `q` (query) function.
+start by identifying relevant files with the `q` (query) function, \
+or with the `s` (search) function when you know what to look for but not where, \
+or with the `S` (semantic search) function when you do not know the words either.
 Next, use the `F` (read file) function to understand their contents.
 To learn the file hierarchy, use the `f` (list files) function.
+To follow the control flow, use the `c` (call graph) function.
+To learn how the modules are layered, use the `m` (module graph) function.
+To triage technical debt, use the `t` (annotations) function.
+To learn why a line is the way it is, use the `b` (blame) function, \
+then the `G` (show commit) function on the commit it points to.
 To understand the overall structure, read the `README.md` and CI files.
 They will give you a hint of the overall structure.
 
@@ -30,10 +38,91 @@ or whenever you see reasonable.
 
 When trying to edit the files, just provide the patch, without citing the full source.
 If the tree is dirty, ask user's permissions before doing any edits.
+To see what the user is in the middle of changing, use the `w` (status) and `W` (working diff) functions.
+When asked to commit, read the staged diff first, then draft the message in the style of the recent `g` (log) \
+and pass it to the `C` (commit) function, which lets the user approve or edit it.
+When there are merge conflicts, list them with the `x` (conflicts) function, \
+read the commits behind each side with `G` to learn what each meant to do, \
+then propose a resolution that keeps both intents with the `X` (resolve) function.
 
 Remember, you've got this! Believe in your abilities and provide the best assistance possible.
 ";
 
+pub const COMMIT_MESSAGE_PROMPT: &str = "\
+You write git commit messages. The user gives you the staged changes \
+and the recent history of the repository.
+Reply with the commit message only, with no quotes, fences, or commentary around it.
+Follow the conventional commits format: `type(scope): summary`, \
+where the type is one of feat, fix, refactor, perf, docs, test, build, ci, style, or chore, \
+and the scope is optional. Keep the summary under 72 characters, in the imperative mood, \
+with no period at the end. Match the wording and the scopes of the recent history where it is consistent.
+If the change is not obvious from the summary alone, add a body after a blank line \
+explaining what changed and why, wrapped at 72 characters.
+Parts of the patch may be cut, trust the stats for the overall shape of the change.
+";
+
+pub const REVIEW_PROMPT: &str = "\
+You are reviewing a change to a codebase, as an expert programmer reviewing a colleague's work. \
+You have access to the repository through the provided functions, which can only read it.
+The user gives you which change to review and its stats. Read the patch of each file \
+with the `D` (diff revisions) function, or the `W` (working diff) function for uncommitted changes, \
+passing the path to keep it short. Read the surrounding code with the `F` (read file) \
+and `q` (query) functions wherever the patch alone does not tell whether the change is right.
+
+Look for bugs, unhandled errors, security problems, races, broken contracts with the callers, \
+and code that does not match the conventions of the files around it. \
+Do not report matters of taste, and do not praise.
+
+When done, reply with a JSON array and nothing else, one object per finding: \
+{\"file\": \"relative/path\", \"line\": 12, \"severity\": \"error\", \"message\": \"...\"}.
+The line is in the new version of the file. The severity is `error` for what is broken, \
+`warning` for what is likely to break or to mislead, and `info` for the rest. \
+Keep each message to a sentence or two, saying what is wrong and what to do instead.
+Reply with `[]` if there is nothing to report.
+";
+
+pub const CHANGELOG_PROMPT: &str = "\
+You write release notes. The user gives you the commits of a release, already grouped, \
+each with its short hash, summary, and the beginning of its message.
+Reply with Markdown for a section of a `CHANGELOG.md`, with no heading for the release itself \
+and no fences around it: a `### Group` heading for each group that has anything to say, \
+then one bullet per user-facing change.
+Write for the people who use the project, not for the ones who wrote it: \
+say what changed for them, in the past tense, and merge the commits that make one change together. \
+Leave out what users cannot notice, like tests, CI, refactoring, and formatting, \
+unless nothing else is left in the release.
+End every bullet with the short hashes of its commits in parentheses, like `(1a2b3c4, 5d6e7f8)`.
+";
+
+pub const BISECT_PROMPT: &str = "\
+You help find the cause of a regression. The user bisected it to one commit, \
+with a command that passes before the commit and fails after it. \
+You get the command, the end of its output at the commit, and the commit itself.
+Explain what in the commit most likely breaks the command, pointing at the files and lines in the patch, \
+and suggest what to change to fix it. If the commit does not explain the failure, say so, \
+and say what else could make the command fail. Be concise: you're an expert programmer talking to an expert programmer.
+";
+
+/// The names of the functions that only look at the codebase.
+/// A function left out of here is not offered when nothing should change, so new ones are safe by default.
+const READ_ONLY_FUNCTIONS: &[&str] = &[
+    "q", "f", "F", "g", "G", "c", "m", "t", "s", "S", "b", "w", "W", "D", "k", "R", "h", "H", "o",
+    "x",
+];
+
+/// The functions that only look at the codebase, for when nothing should change.
+pub fn read_only_functions() -> serde_json::Value {
+    let mut functions = all_functions();
+    if let Some(functions) = functions.as_array_mut() {
+        functions.retain(|function| {
+            function["function"]["name"]
+                .as_str()
+                .is_some_and(|name| READ_ONLY_FUNCTIONS.contains(&name))
+        });
+    }
+    functions
+}
+
 /// List all the functions as a JSON schema understood by the model.
 pub fn all_functions() -> serde_json::Value {
     json!([
@@ -48,6 +137,10 @@ pub fn all_functions() -> serde_json::Value {
                         "path": {
                             "type": "string",
                             "description": "relative path to the file to parse, or to a directory to parse all the files in"
+                        },
+                        "rev": {
+                            "type": "string",
+                            "description": "revision to read from, like a commit hash, branch or tag, instead of the working copy"
                         }
                     },
                     "required": ["path"],
@@ -65,6 +158,10 @@ pub fn all_functions() -> serde_json::Value {
                         "path": {
                             "type": "string",
                             "description": "relative path to the directory to look into"
+                        },
+                        "rev": {
+                            "type": "string",
+                            "description": "revision to list from, like a commit hash, branch or tag, instead of the working copy"
                         }
                     },
                     "required": ["path"],
@@ -82,6 +179,19 @@ pub fn all_functions() -> serde_json::Value {
                         "path": {
                             "type": "string",
                             "description": "relative path to the file to read"
+                        },
+                        "skeleton": {
+                            "type": "boolean",
+                            "description": "fold function bodies to `{ … }`, keeping signatures, types, doc comments and line numbers"
+                        },
+                        "expand": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "names of the functions to keep unfolded in the skeleton"
+                        },
+                        "rev": {
+                            "type": "string",
+                            "description": "revision to read from, like a commit hash, branch or tag, instead of the working copy"
                         }
                     },
                     "required": ["path"],
@@ -92,27 +202,510 @@ pub fn all_functions() -> serde_json::Value {
             "type": "function",
             "function": {
                 "name": "g",
-                "description": "show commits log",
-                "parameters": {},
+                "description": "show commits log, newest first",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "max_count": {
+                            "type": "integer",
+                            "description": "how many commits to show, 50 by default"
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "description": "how many matching commits to skip, to see the next page"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path, to only show the commits that touched it"
+                        },
+                        "author": {
+                            "type": "string",
+                            "description": "part of the author name or email"
+                        },
+                        "since": {
+                            "type": "string",
+                            "description": "only commits made on this date or later, as YYYY-MM-DD"
+                        },
+                        "until": {
+                            "type": "string",
+                            "description": "only commits made on this date or earlier, as YYYY-MM-DD"
+                        },
+                        "message": {
+                            "type": "string",
+                            "description": "part of the commit message, case insensitive"
+                        },
+                        "rev": {
+                            "type": "string",
+                            "description": "revision to start from, like a branch or tag, HEAD by default"
+                        },
+                        "first_parent": {
+                            "type": "boolean",
+                            "description": "follow only the first parent of merges, false by default"
+                        }
+                    },
+                    "required": [],
+                },
             }
         },
         {
             "type": "function",
             "function": {
                 "name": "G",
-                "description": "show certain commit in details",
+                "description": "show certain commit in details: the whole message, the stats and the patch",
                 "parameters": {
                     "type": "object",
                     "properties": {
                         "hash": {
                             "type": "string",
-                            "description": "hash-like of the commit to show"
+                            "description": "hash-like of the commit to show, or any revision like `HEAD~2` or a tag"
+                        },
+                        "parent": {
+                            "type": "integer",
+                            "description": "for a merge, which parent to show the patch against, 1 by default"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to limit the patch to"
                         }
                     },
                     "required": ["hash"],
                 },
             }
         },
+        {
+            "type": "function",
+            "function": {
+                "name": "c",
+                "description": "show the call graph around a function: what it calls, or what calls it",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "name of the function or method"
+                        },
+                        "direction": {
+                            "type": "string",
+                            "enum": ["callees", "callers"],
+                            "description": "`callees` for what the function calls, `callers` for what reaches it"
+                        },
+                        "depth": {
+                            "type": "integer",
+                            "description": "how many calls away to follow, 2 by default"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the directory to analyze, the current directory by default"
+                        }
+                    },
+                    "required": ["name", "direction"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "m",
+                "description": "show which modules import which, and the import cycles; or, given both `from` and `to`, tell whether one depends on the other",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the directory to analyze, the current directory by default"
+                        },
+                        "from": {
+                            "type": "string",
+                            "description": "relative path to the depending file or directory like `src/io`, or a module path like `io` or `crate::io`"
+                        },
+                        "to": {
+                            "type": "string",
+                            "description": "relative path to the file or directory depended upon like `src/openai`, or a module path like `openai`"
+                        },
+                        "format": {
+                            "type": "string",
+                            "enum": ["text", "dot", "json", "mermaid"],
+                            "description": "`text` by default"
+                        }
+                    },
+                    "required": [],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "t",
+                "description": "list comment annotations like TODO and FIXME, with who wrote each and when",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the file or directory to scan, the current directory by default"
+                        },
+                        "tags": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "tags to look for, `TODO`, `FIXME`, `HACK`, `XXX` and `SAFETY` by default"
+                        },
+                        "blame": {
+                            "type": "boolean",
+                            "description": "whether to attribute each annotation to its author, true by default"
+                        }
+                    },
+                    "required": [],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "s",
+                "description": "search the code by keywords, ranking functions and other pieces of files by relevance",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "keywords or identifiers to look for, like `retry backoff`"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the directory to search in, the current directory by default"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "how many results to show, 10 by default"
+                        }
+                    },
+                    "required": ["query"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "S",
+                "description": "search the code by meaning, finding related pieces even when they share no words with the query",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "natural-language description of what to look for"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the directory to search in, the current directory by default"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "how many results to show, 10 by default"
+                        }
+                    },
+                    "required": ["query"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "b",
+                "description": "show which commit last touched each line of a file, grouped into hunks",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the file to blame"
+                        },
+                        "start_line": {
+                            "type": "integer",
+                            "description": "first line to blame, one-based, the beginning of the file by default"
+                        },
+                        "end_line": {
+                            "type": "integer",
+                            "description": "last line to blame, inclusive, the end of the file by default"
+                        }
+                    },
+                    "required": ["path"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "w",
+                "description": "show the working tree status: staged, unstaged, untracked and conflicted files",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to limit the status to"
+                        }
+                    },
+                    "required": [],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "W",
+                "description": "show the uncommitted changes as a diff against the last commit, with untracked files listed by size",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "staged": {
+                            "type": "boolean",
+                            "description": "only show what is staged in the index, false by default"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to limit the diff to"
+                        },
+                        "stat": {
+                            "type": "boolean",
+                            "description": "only show how many lines changed in each file, false by default"
+                        }
+                    },
+                    "required": [],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "D",
+                "description": "show the diff between two revisions, such as a whole branch",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "from": {
+                            "type": "string",
+                            "description": "revision to compare from, or a whole range like `main..feature`, `main...feature` or `HEAD~3`"
+                        },
+                        "to": {
+                            "type": "string",
+                            "description": "revision to compare to, HEAD by default"
+                        },
+                        "merge_base": {
+                            "type": "boolean",
+                            "description": "compare from where the two revisions diverged, like `from...to`, false by default"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to limit the diff to"
+                        },
+                        "stat": {
+                            "type": "boolean",
+                            "description": "only show how many lines changed in each file, false by default"
+                        }
+                    },
+                    "required": ["from"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "k",
+                "description": "find the commits that added or removed occurrences of a string, like `git log -S`",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "string to look for"
+                        },
+                        "regex": {
+                            "type": "boolean",
+                            "description": "treat the query as a regular expression, false by default"
+                        },
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to limit the search to"
+                        },
+                        "rev": {
+                            "type": "string",
+                            "description": "revision to start from, HEAD by default"
+                        },
+                        "max_count": {
+                            "type": "integer",
+                            "description": "how many commits to show, 10 by default"
+                        }
+                    },
+                    "required": ["query"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "R",
+                "description": "list branches and tags, with their last commit, how far they are from HEAD, and what they track",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "kind": {
+                            "type": "string",
+                            "enum": ["branches", "remotes", "tags"],
+                            "description": "only list refs of this kind, all of them by default"
+                        },
+                        "pattern": {
+                            "type": "string",
+                            "description": "only list refs with names containing this"
+                        }
+                    },
+                    "required": [],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "h",
+                "description": "show the commits that changed a function, with the diff of just that function, newest first",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the file with the function as it is now"
+                        },
+                        "name": {
+                            "type": "string",
+                            "description": "name of the function or method"
+                        },
+                        "rev": {
+                            "type": "string",
+                            "description": "revision to start from, HEAD by default"
+                        },
+                        "max_count": {
+                            "type": "integer",
+                            "description": "how many commits to show, 10 by default"
+                        }
+                    },
+                    "required": ["path", "name"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "H",
+                "description": "rank the files and functions that change most often and are most complex, which are the riskiest to touch",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to limit the analysis to"
+                        },
+                        "days": {
+                            "type": "integer",
+                            "description": "how far back to look into the history, 365 days by default"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "how many files to show, 15 by default"
+                        }
+                    },
+                    "required": [],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "o",
+                "description": "show who owns a file or directory: by lines they last touched, by recent commits, and the bus factor; useful to find reviewers",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the file or directory, the current directory by default"
+                        },
+                        "days": {
+                            "type": "integer",
+                            "description": "how far back to count the commits, 180 days by default"
+                        }
+                    },
+                    "required": [],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "C",
+                "description": "stage the given paths and commit them with the message, once the user approves or edits it; never pushes",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "paths": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "relative paths to stage before committing, nothing more by default"
+                        },
+                        "message": {
+                            "type": "string",
+                            "description": "commit message: a short summary line, a blank line, and the details if needed"
+                        }
+                    },
+                    "required": ["message"],
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "x",
+                "description": "list the conflicts in the working tree, with ours, base and theirs of each, and the commits on either side that touched it",
+                "parameters": {
+                    "type": "object",
+                    "properties": {},
+                },
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "X",
+                "description": "propose how to resolve conflicts in a file; the user accepts each one or not, and the file is marked resolved once no conflict is left; a file without markers, like a binary one or one deleted on a side, takes no resolutions and is marked resolved as it is if the user agrees",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "relative path to the conflicted file"
+                        },
+                        "resolutions": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "conflict": {
+                                        "type": "integer",
+                                        "description": "number of the conflict in the file, as `x` lists it, starting from 1"
+                                    },
+                                    "text": {
+                                        "type": "string",
+                                        "description": "what the whole conflict region, markers included, is replaced with"
+                                    }
+                                },
+                                "required": ["conflict", "text"],
+                            },
+                            "description": "one proposal per conflict to resolve"
+                        }
+                    },
+                    "required": ["path", "resolutions"],
+                },
+            }
+        },
         {
             "type": "function",
             "function": {
@@ -141,3 +734,22 @@ pub fn all_functions() -> serde_json::Value {
         // }}
     ])
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_only_functions_change_nothing() {
+        let names: Vec<String> = read_only_functions()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|function| function["function"]["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names.len(), READ_ONLY_FUNCTIONS.len());
+        for name in ["C", "X", "p"] {
+            assert!(!names.iter().any(|each| each == name));
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! When and how long to wait before trying a request again.
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+
+use super::ErrorDetails;
+
+/// How many times to send a request before giving up.
+pub const MAX_ATTEMPTS: u32 = 6;
+
+/// The wait after the first failure, doubling with each one after.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest wait between two attempts, whatever the server asks for.
+const MAX_DELAY: Duration = Duration::from_secs(120);
+
+/// Error codes that no amount of waiting fixes, even when they come with a 429.
+const FATAL_CODES: &[&str] = &[
+    "insufficient_quota",
+    "invalid_api_key",
+    "billing_hard_limit_reached",
+    "account_deactivated",
+    "context_length_exceeded",
+    "model_not_found",
+];
+
+/// Whether the failed request is worth sending again.
+pub fn is_retryable(status: StatusCode, details: Option<&ErrorDetails>) -> bool {
+    if let Some(code) = details.and_then(|details| details.code.as_deref()) {
+        if FATAL_CODES.contains(&code) {
+            return false;
+        }
+    }
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::CONFLICT
+        || status.is_server_error()
+}
+
+/// Whether the request failed on the way, rather than being refused by the server.
+pub fn is_transient(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+}
+
+/// `1s`, `6m0s`, `20ms`, or `1h2m3.5s`, the way the rate limit headers say when they reset.
+fn parse_reset(text: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|char: char| !char.is_ascii_digit() && char != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|char: char| char.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_end] {
+            "ms" => 0.001,
+            "s" | "" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        total += number * seconds;
+        rest = &rest[unit_end..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+/// How long the server asks to wait, by `Retry-After` or by when the rate limits reset.
+pub fn delay_from_headers(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(millis) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(millis.max(0.0) / 1000.0));
+    }
+    if let Some(value) = header("retry-after") {
+        if let Ok(seconds) = value.trim().parse::<f64>() {
+            return Some(Duration::from_secs_f64(seconds.max(0.0)));
+        }
+        // It may also be a date.
+        if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+            let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+            return Some(Duration::from_secs(seconds as u64));
+        }
+    }
+    // Only the limits that ran out matter.
+    [
+        ("requests", "x-ratelimit-reset-requests"),
+        ("tokens", "x-ratelimit-reset-tokens"),
+    ]
+    .into_iter()
+    .filter(|(kind, _)| header(&format!("x-ratelimit-remaining-{kind}")) == Some("0"))
+    .filter_map(|(_, reset)| header(reset).and_then(parse_reset))
+    .max()
+}
+
+/// A number that is different every time, enough to keep the clients from retrying in lockstep.
+fn random_fraction() -> f64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// How long to wait after the attempt failed: what the server asks for if it does,
+/// or else twice as long as the last time, somewhere in the upper half of that at random.
+pub fn delay(attempt: u32, asked: Option<Duration>) -> Duration {
+    if let Some(asked) = asked {
+        return asked.min(MAX_DELAY);
+    }
+    let exponential = BASE_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(MAX_DELAY);
+    exponential.mul_f64(0.5 + random_fraction() / 2.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reset_durations() {
+        assert_eq!(parse_reset("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(
+            parse_reset("1h2m3.5s"),
+            Some(Duration::from_secs_f64(3723.5))
+        );
+        assert_eq!(parse_reset("soon"), None);
+        assert_eq!(parse_reset(""), None);
+    }
+
+    #[test]
+    fn delays_from_headers() {
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+        assert_eq!(
+            delay_from_headers(&headers(&[("retry-after", "7")])),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            delay_from_headers(&headers(&[("retry-after-ms", "250"), ("retry-after", "7")])),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(
+            delay_from_headers(&headers(&[
+                ("x-ratelimit-remaining-requests", "0"),
+                ("x-ratelimit-reset-requests", "2s"),
+                ("x-ratelimit-remaining-tokens", "100"),
+                ("x-ratelimit-reset-tokens", "1m"),
+            ])),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(delay_from_headers(&headers(&[])), None);
+
+        for attempt in 1..10 {
+            let delay = delay(attempt, None);
+            assert!(delay >= BASE_DELAY / 2 && delay <= MAX_DELAY);
+        }
+        assert_eq!(delay(1, Some(Duration::from_secs(999))), MAX_DELAY);
+    }
+
+    #[test]
+    fn fatal_codes_are_not_retried() {
+        let details = |code: &str| ErrorDetails {
+            code: Some(code.into()),
+            message: None,
+            param: None,
+            kind: String::new(),
+        };
+        let quota = details("insufficient_quota");
+        let limited = details("rate_limit_exceeded");
+        assert!(!is_retryable(StatusCode::TOO_MANY_REQUESTS, Some(&quota)));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS, Some(&limited)));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY, None));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST, None));
+    }
+}
@@ -108,7 +108,7 @@ pub struct Tool {
 
 #[derive(Deserialize, Debug)]
 pub struct ErrorDetails {
-    pub code: String,
+    pub code: Option<String>,
     pub message: Option<String>,
     pub param: Option<String>,
     #[serde(rename = "type")]
@@ -137,3 +137,73 @@ pub enum CompletionResponse {
     Success(SuccessfulCompletionResponse),
     Failure(ErroneousCompletionResponse),
 }
+
+/// A piece of a function call request, as it arrives in a stream.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ToolCallRequestFunctionDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+/// A piece of a function call request, as it arrives in a stream.
+/// The first piece of each call has its id and name, the rest add to the arguments.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ToolCallRequestDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<ToolCallRequestFunctionDelta>,
+}
+
+/// What a chunk of a stream adds to the message.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct MessageDelta {
+    pub role: Option<MessageRole>,
+    pub content: Option<String>,
+    pub tool_calls: Option<Vec<ToolCallRequestDelta>>,
+    pub refusal: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompletionChunkChoice {
+    pub index: usize,
+    #[serde(default)]
+    pub delta: MessageDelta,
+    pub finish_reason: Option<FinishReason>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CompletionChunk {
+    pub id: String,
+    pub choices: Vec<CompletionChunkChoice>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CompletionChunkResponse {
+    Success(CompletionChunk),
+    Failure(ErroneousCompletionResponse),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Embedding {
+    /// Some servers leave it out, and return the vectors in order.
+    #[serde(default)]
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SuccessfulEmbeddingResponse {
+    #[serde(default)]
+    pub object: String,
+    pub data: Vec<Embedding>,
+    #[serde(default)]
+    pub model: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum EmbeddingResponse {
+    Success(SuccessfulEmbeddingResponse),
+    Failure(ErroneousCompletionResponse),
+}
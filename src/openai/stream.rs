@@ -0,0 +1,167 @@
+//! Server-sent events, and putting the message back together from them.
+use super::{
+    CompletionChoice, CompletionChunk, FinishReason, Message, MessageRole, ToolCallRequest,
+    ToolCallRequestFunction,
+};
+
+/// Bytes as they come off the wire, cut into the `data` of each event.
+#[derive(Default)]
+pub struct Events {
+    pending: Vec<u8>,
+}
+
+impl Events {
+    /// Take in more bytes, and give out the data of every event they complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut result = Vec::new();
+        // An event ends with a blank line, which may be `\n\n` or `\r\n\r\n`.
+        while let Some((end, separator)) = find_blank_line(&self.pending) {
+            let event: Vec<u8> = self.pending.drain(..end + separator).collect();
+            let event = String::from_utf8_lossy(&event[..end]);
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            if !data.is_empty() {
+                result.push(data.join("\n"));
+            }
+        }
+        result
+    }
+}
+
+/// Where the first blank line starts, and how long the separator is.
+fn find_blank_line(bytes: &[u8]) -> Option<(usize, usize)> {
+    let lf = bytes.windows(2).position(|window| window == b"\n\n");
+    let crlf = bytes.windows(4).position(|window| window == b"\r\n\r\n");
+    match (lf, crlf) {
+        (Some(lf), Some(crlf)) if crlf < lf => Some((crlf, 4)),
+        (Some(lf), _) => Some((lf, 2)),
+        (None, Some(crlf)) => Some((crlf, 4)),
+        (None, None) => None,
+    }
+}
+
+/// The message of the first choice, so far.
+#[derive(Default)]
+pub struct Assembly {
+    content: Option<String>,
+    refusal: Option<String>,
+    tool_calls: Vec<ToolCallRequest>,
+    finish_reason: Option<FinishReason>,
+}
+
+impl Assembly {
+    /// Add the chunk to the message, and give out the text it adds, if any.
+    pub fn apply(&mut self, chunk: CompletionChunk) -> Option<String> {
+        let choice = chunk.choices.into_iter().find(|choice| choice.index == 0)?;
+        if choice.finish_reason.is_some() {
+            self.finish_reason = choice.finish_reason;
+        }
+        let delta = choice.delta;
+        if let Some(refusal) = delta.refusal {
+            self.refusal
+                .get_or_insert_with(String::new)
+                .push_str(&refusal);
+        }
+        for call in delta.tool_calls.unwrap_or_default() {
+            while self.tool_calls.len() <= call.index {
+                self.tool_calls.push(ToolCallRequest {
+                    id: String::new(),
+                    kind: Default::default(),
+                    function: ToolCallRequestFunction {
+                        name: String::new(),
+                        arguments: String::new(),
+                    },
+                });
+            }
+            let request = &mut self.tool_calls[call.index];
+            if let Some(id) = call.id {
+                request.id = id;
+            }
+            if let Some(function) = call.function {
+                request
+                    .function
+                    .name
+                    .push_str(&function.name.unwrap_or_default());
+                request
+                    .function
+                    .arguments
+                    .push_str(&function.arguments.unwrap_or_default());
+            }
+        }
+        let content = delta.content.filter(|content| !content.is_empty())?;
+        self.content
+            .get_or_insert_with(String::new)
+            .push_str(&content);
+        Some(content)
+    }
+
+    /// The whole message, as if it came in one piece.
+    pub fn finish(self) -> CompletionChoice {
+        let has_calls = !self.tool_calls.is_empty();
+        // Some servers end the stream without saying why.
+        let finish_reason = self.finish_reason.unwrap_or(if has_calls {
+            FinishReason::Call
+        } else {
+            FinishReason::Done
+        });
+        CompletionChoice {
+            index: 0,
+            finish_reason,
+            message: Message {
+                role: MessageRole::Assistant,
+                content: self.content,
+                tool_calls: has_calls.then_some(self.tool_calls),
+                refusal: self.refusal,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn events_across_reads() {
+        let mut events = Events::default();
+        assert!(events.push(b"data: {\"a\"").is_empty());
+        assert_eq!(
+            events.push(b": 1}\n\n: comment\n\ndata: [DONE]\r\n\r\n"),
+            vec!["{\"a\": 1}", "[DONE]"]
+        );
+        assert!(events.push(b"data: x\n").is_empty());
+        assert_eq!(events.push(b"data: y\n\n"), vec!["x\ny"]);
+    }
+
+    #[test]
+    fn message_from_chunks() {
+        let chunks = [
+            r#"{"id":"1","choices":[{"index":0,"delta":{"role":"assistant","content":"Let me"}}]}"#,
+            r#"{"id":"1","choices":[{"index":0,"delta":{"content":" look."}}]}"#,
+            r#"{"id":"1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"c1","type":"function","function":{"name":"F","arguments":""}}]}}]}"#,
+            r#"{"id":"1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"path\":"}}]}}]}"#,
+            r#"{"id":"1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"a\"}"}}]}}]}"#,
+            r#"{"id":"1","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+        ];
+        let mut assembly = Assembly::default();
+        let mut shown = String::new();
+        for chunk in chunks {
+            let chunk: CompletionChunk = serde_json::from_str(chunk).unwrap();
+            shown.extend(assembly.apply(chunk));
+        }
+        let choice = assembly.finish();
+        assert_eq!(shown, "Let me look.");
+        assert_eq!(choice.finish_reason, FinishReason::Call);
+        assert_eq!(choice.message.content.as_deref(), Some("Let me look."));
+        let calls = choice.message.tool_calls.unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "c1");
+        assert_eq!(calls[0].function.name, "F");
+        assert_eq!(calls[0].function.arguments, "{\"path\":\"a\"}");
+    }
+}